@@ -1,48 +1,438 @@
-use std::str::FromStr;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt,
+    io::{BufRead, Write},
+    str::FromStr,
+};
 
 fn main() {
-    let simulation_ops = std::io::stdin()
-        .lines()
-        .map(Result::unwrap)
-        .filter(|s| !s.is_empty())
-        .map(|s| s.parse::<Instruction>().unwrap())
-        .flat_map(Instruction::to_simulation_ops);
+    let mut crt_config = CrtConfig::default();
+    let mut debug_program_path = None;
+    let mut pbm_path = None;
+    let mut pgm_path = None;
+    let mut assemble_image = false;
 
-    let mut sim = Simulation {
-        ops_iter: simulation_ops,
-        state: SimulationState::default(),
-    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .unwrap_or_else(|| panic!("missing value for {arg}"))
+        };
+        let mut number = || -> usize {
+            value()
+                .parse()
+                .unwrap_or_else(|_| panic!("{arg} requires a number"))
+        };
+        // the CRT needs at least one pixel
+        let mut screen_size = || match number() {
+            0 => panic!("{arg} must be at least 1"),
+            n => n,
+        };
+
+        match arg.as_str() {
+            "--columns" => crt_config.columns = screen_size(),
+            "--rows" => crt_config.rows = screen_size(),
+            "--sprite-width" => crt_config.sprite_width = number(),
+            // the program comes from a file, so that stdin is free for debugger commands
+            "--debug" => debug_program_path = Some(value()),
+            "--export-pbm" => pbm_path = Some(value()),
+            "--export-pgm" => pgm_path = Some(value()),
+            "--assemble" => assemble_image = true,
+            _ => panic!("unknown argument: {arg}"),
+        }
+    }
+
+    if assemble_image {
+        let input = std::io::read_to_string(std::io::stdin()).unwrap();
+        let image = Framebuffer::parse(&input, crt_config).unwrap_or_else(|e| panic!("{e}"));
+        let program = assemble(&image, crt_config.sprite_width).unwrap_or_else(|e| panic!("{e}"));
+
+        let mut sim = Simulation::new(program.clone(), crt_config);
+        sim.step_frame().unwrap();
+        assert_eq!(
+            sim.state().crt,
+            image,
+            "assembled program draws a different image"
+        );
+
+        for instr in program {
+            println!("{instr}");
+        }
+        return;
+    }
+
+    if let Some(path) = debug_program_path {
+        let program = parse_program(&std::fs::read_to_string(path).unwrap());
+
+        run_debugger(
+            Simulation::new(program, crt_config),
+            std::io::stdin().lock(),
+        );
+        return;
+    }
+
+    let program = parse_program(&std::io::read_to_string(std::io::stdin()).unwrap());
+
+    let mut sim = Simulation::new(program, crt_config);
 
     let mut part1_solution = 0;
     for inspection_point in [20, 60, 100, 140, 180, 220] {
         sim.step_until(|st| st.started_cycle_counter == inspection_point)
-            .expect("not enough instructions");
+            .unwrap_or_else(|e| panic!("cannot reach cycle {inspection_point}: {e}"));
 
-        let part1_signal_strength = (inspection_point as i64) * sim.state().register_x;
+        let part1_signal_strength = (inspection_point as i64) * sim.state().register(Register::X);
         part1_solution += part1_signal_strength;
     }
     println!("{part1_solution}");
 
-    sim.step_remaining();
-    for line in sim.state().crt.chunks_exact(CRT_COLUMNS) {
-        for c in line.iter().map(|is_lit| if *is_lit { '#' } else { '.' }) {
-            print!("{c}");
+    // a short program leaves the rest of the screen dark, a looping one stops after the frame
+    match sim.step_frame() {
+        Ok(()) | Err(SimError::ProgramEnded) => {}
+        Err(e) => panic!("{e} at cycle {}", sim.state().started_cycle_counter),
+    }
+    match decode_crt(&sim.state().crt) {
+        Ok(letters) => println!("{letters}"),
+        Err(UnknownGlyph { cell, pattern }) => {
+            eprintln!("unknown glyph in cell {cell}:\n{pattern}");
+            print!("{}", sim.state().crt.render());
+        }
+    }
+
+    if let Some(path) = pbm_path {
+        std::fs::write(path, sim.state().crt.to_pbm()).unwrap();
+    }
+    if let Some(path) = pgm_path {
+        std::fs::write(path, sim.state().crt.to_pgm()).unwrap();
+    }
+}
+
+fn parse_program(input: &str) -> Vec<Instruction> {
+    input
+        .lines()
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse::<Instruction>().unwrap())
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Breakpoint {
+    Cycle(usize),
+    RegisterValue(Register, i64),
+}
+
+impl Breakpoint {
+    // register breakpoints only fire when the register changes to the value, so that
+    // execution can continue past a value that is held for a while
+    fn is_hit(self, prev_registers: &[i64], state: &SimulationState) -> bool {
+        match self {
+            Breakpoint::Cycle(cycle) => state.started_cycle_counter == cycle,
+            Breakpoint::RegisterValue(reg, value) => {
+                state.register(reg) == value && prev_registers[reg as usize] != value
+            }
+        }
+    }
+}
+
+const DEBUGGER_HELP: &str = "\
+step [n]            run n cycles (default 1)
+continue            run until a breakpoint is hit, the frame is complete or the program ends
+break cycle <n>     stop when cycle n starts
+break <reg> <v>     stop when register reg (w, x, y or z) changes to v
+breakpoints         list breakpoints
+clear               delete all breakpoints
+regs                show registers, program counter and cycle
+crt                 show the CRT
+watch               toggle printing the signal strength of every cycle
+quit                exit the debugger";
+
+fn run_debugger(mut sim: Simulation, commands: impl BufRead) {
+    let mut breakpoints = Vec::new();
+    let mut watch_signal = false;
+
+    let prompt = || {
+        print!("(day10) ");
+        std::io::stdout().flush().unwrap();
+    };
+
+    prompt();
+    for line in commands.lines().map(Result::unwrap) {
+        let tokens = line.split_ascii_whitespace().collect::<Vec<_>>();
+
+        // every step reports on each new cycle it enters, then checks breakpoints
+        let mut last_cycle = sim.state().started_cycle_counter;
+        let mut last_registers = sim.state().registers;
+        let mut on_step =
+            |st: &SimulationState, stop: &mut dyn FnMut(&[i64], &SimulationState) -> bool| {
+                if st.started_cycle_counter == last_cycle {
+                    return false;
+                }
+                last_cycle = st.started_cycle_counter;
+                let prev_registers = std::mem::replace(&mut last_registers, st.registers);
+
+                if watch_signal {
+                    let signal = st.started_cycle_counter as i64 * st.register(Register::X);
+                    println!(
+                        "cycle {}: signal strength {signal}",
+                        st.started_cycle_counter
+                    );
+                }
+                stop(&prev_registers, st)
+            };
+
+        match tokens.as_slice() {
+            [] => {}
+            ["step" | "s", rest @ ..] => {
+                let n = rest.first().map_or(Ok(1), |n| n.parse::<usize>());
+                let Ok(n) = n else {
+                    println!("invalid cycle count");
+                    prompt();
+                    continue;
+                };
+
+                let target = sim.state().started_cycle_counter + n;
+                let res = sim
+                    .step_until(|st| on_step(st, &mut |_, st| st.started_cycle_counter == target));
+                if let Err(e) = res {
+                    println!("{e} at cycle {}", sim.state().started_cycle_counter);
+                }
+            }
+            ["continue" | "c"] => {
+                // looping programs never end, so stop at the end of each frame too
+                let frames_drawn = sim.state().crt_frames_drawn;
+                let res = sim.step_until(|st| {
+                    st.crt_frames_drawn > frames_drawn
+                        || on_step(st, &mut |prev_registers, st| {
+                            breakpoints
+                                .iter()
+                                .any(|b: &Breakpoint| b.is_hit(prev_registers, st))
+                        })
+                });
+                let cycle = sim.state().started_cycle_counter;
+                match res {
+                    Ok(()) if sim.state().crt_frames_drawn > frames_drawn => {
+                        println!("frame complete at cycle {cycle}")
+                    }
+                    Ok(()) => println!("breakpoint hit at cycle {cycle}"),
+                    Err(e) => println!("{e} at cycle {cycle}"),
+                }
+            }
+            ["break" | "b", "cycle", n] => match n.parse() {
+                Ok(n) => breakpoints.push(Breakpoint::Cycle(n)),
+                Err(_) => println!("invalid cycle"),
+            },
+            ["break" | "b", reg, v] => {
+                let reg = Register::ALL
+                    .into_iter()
+                    .find(|r| r.name().to_string() == *reg);
+                match (reg, v.parse()) {
+                    (Some(reg), Ok(v)) => breakpoints.push(Breakpoint::RegisterValue(reg, v)),
+                    _ => println!("invalid register breakpoint"),
+                }
+            }
+            ["breakpoints"] => {
+                for (idx, b) in breakpoints.iter().enumerate() {
+                    println!("{idx}: {b:?}");
+                }
+            }
+            ["clear"] => breakpoints.clear(),
+            ["regs" | "r"] => {
+                let st = sim.state();
+                for reg in Register::ALL {
+                    println!("{} = {}", reg.name(), st.register(reg));
+                }
+                println!("pc = {}", st.program_counter);
+                println!("cycle = {}", st.started_cycle_counter);
+            }
+            ["crt"] => print!("{}", sim.state().crt.render()),
+            ["watch" | "w"] => {
+                watch_signal = !watch_signal;
+                println!("watching signal strength: {watch_signal}");
+            }
+            ["quit" | "q"] => return,
+            ["help" | "h"] => println!("{DEBUGGER_HELP}"),
+            _ => println!("unknown command, try help"),
+        }
+
+        prompt();
+    }
+}
+
+// Capital letters in the puzzle font, 4 pixels wide, one row per line
+const GLYPHS: [(char, &str); 18] = [
+    ('A', ".##.\n#..#\n#..#\n####\n#..#\n#..#"),
+    ('B', "###.\n#..#\n###.\n#..#\n#..#\n###."),
+    ('C', ".##.\n#..#\n#...\n#...\n#..#\n.##."),
+    ('E', "####\n#...\n###.\n#...\n#...\n####"),
+    ('F', "####\n#...\n###.\n#...\n#...\n#..."),
+    ('G', ".##.\n#..#\n#...\n#.##\n#..#\n.###"),
+    ('H', "#..#\n#..#\n####\n#..#\n#..#\n#..#"),
+    ('I', ".###\n..#.\n..#.\n..#.\n..#.\n.###"),
+    ('J', "..##\n...#\n...#\n...#\n#..#\n.##."),
+    ('K', "#..#\n#.#.\n##..\n#.#.\n#.#.\n#..#"),
+    ('L', "#...\n#...\n#...\n#...\n#...\n####"),
+    ('O', ".##.\n#..#\n#..#\n#..#\n#..#\n.##."),
+    ('P', "###.\n#..#\n#..#\n###.\n#...\n#..."),
+    ('R', "###.\n#..#\n#..#\n###.\n#.#.\n#..#"),
+    ('S', ".###\n#...\n#...\n.##.\n...#\n###."),
+    ('U', "#..#\n#..#\n#..#\n#..#\n#..#\n.##."),
+    ('Y', "#...\n#...\n.#.#\n..#.\n..#.\n..#."),
+    ('Z', "####\n...#\n..#.\n.#..\n#...\n####"),
+];
+const GLYPH_CELL_WIDTH: usize = 5; // 4 pixels plus spacing
+
+#[derive(Debug)]
+struct UnknownGlyph {
+    cell: usize,
+    pattern: String,
+}
+
+fn decode_crt(crt: &Framebuffer) -> Result<String, UnknownGlyph> {
+    (0..crt.columns / GLYPH_CELL_WIDTH)
+        .map(|cell| {
+            let pattern = crt
+                .lines()
+                .map(|line| {
+                    let start = cell * GLYPH_CELL_WIDTH;
+                    line[start..start + GLYPH_CELL_WIDTH - 1]
+                        .iter()
+                        .map(|is_lit| if *is_lit { '#' } else { '.' })
+                        .collect::<String>()
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            GLYPHS
+                .iter()
+                .find(|(_, glyph)| *glyph == pattern)
+                .map(|(letter, _)| *letter)
+                .ok_or(UnknownGlyph { cell, pattern })
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+enum AssembleError {
+    InvalidImage(String),
+    ConflictingPixel { row: usize, column: usize },
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssembleError::InvalidImage(reason) => write!(f, "invalid image: {reason}"),
+            AssembleError::ConflictingPixel { row, column } => write!(
+                f,
+                "no program can draw the image up to the pixel at row {row}, column {column}"
+            ),
         }
+    }
+}
+
+// Finds a `noop`/`addx` program that makes the CRT draw `image`.
+//
+// Works cycle by cycle over the values register X can hold at each instruction boundary:
+// a `noop` keeps X for one cycle, an `addx` keeps it for two and can then set it to
+// anything. Values further than a sprite width off screen all draw the same, so only a
+// bounded range of X needs to be considered.
+fn assemble(image: &Framebuffer, sprite_width: usize) -> Result<Vec<Instruction>, AssembleError> {
+    let n_pixels = image.pixels.len();
+    let sprite_width = sprite_width as i64;
+    let x_values = -sprite_width..=(image.columns as i64 + sprite_width);
+
+    let draws_right = |cycle: usize, register_x: i64| {
+        let column = (cycle % image.columns) as i64;
+        let sprite_start = register_x - (sprite_width - 1) / 2;
+        let is_lit = (sprite_start..sprite_start + sprite_width).contains(&column);
+        is_lit == image.pixels[cycle]
+    };
+
+    // for each cycle, the values X can hold when an instruction starts on that cycle,
+    // and the previous X value and instruction that led to it
+    let mut boundaries = vec![BTreeMap::new(); n_pixels + 1];
+    boundaries[0].insert(1, (1, Instruction::Noop));
+
+    for cycle in 0..n_pixels {
+        let reachable = boundaries[cycle].keys().copied().collect::<Vec<_>>();
+        for register_x in reachable {
+            if !draws_right(cycle, register_x) {
+                continue;
+            }
 
-        println!();
+            boundaries[cycle + 1]
+                .entry(register_x)
+                .or_insert((register_x, Instruction::Noop));
+
+            if cycle + 2 <= n_pixels && draws_right(cycle + 1, register_x) {
+                for next_x in x_values.clone().filter(|&x| x != register_x) {
+                    let addx = Instruction::Add(Register::X, next_x - register_x);
+                    boundaries[cycle + 2]
+                        .entry(next_x)
+                        .or_insert((register_x, addx));
+                }
+            }
+        }
     }
+
+    let Some(&(mut register_x)) = boundaries[n_pixels].keys().next() else {
+        // every pixel before the furthest reachable boundary can be drawn, but not that one
+        let conflict = (0..n_pixels)
+            .rev()
+            .find(|&cycle| !boundaries[cycle].is_empty())
+            .unwrap_or(0);
+        return Err(AssembleError::ConflictingPixel {
+            row: conflict / image.columns,
+            column: conflict % image.columns,
+        });
+    };
+
+    let mut program = Vec::new();
+    let mut cycle = n_pixels;
+    while cycle > 0 {
+        let (prev_x, instr) = boundaries[cycle][&register_x];
+        program.push(instr);
+        cycle -= instr.cycles();
+        register_x = prev_x;
+    }
+    program.reverse();
+
+    Ok(program)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SimError {
+    // the program counter left the program
+    ProgramEnded,
+    Overflow(Register),
 }
 
-struct Simulation<OpIt> {
-    ops_iter: OpIt,
+impl fmt::Display for SimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimError::ProgramEnded => write!(f, "program ended"),
+            SimError::Overflow(reg) => write!(f, "register {} overflowed", reg.name()),
+        }
+    }
+}
+
+struct Simulation {
+    program: Vec<Instruction>,
+    pending_ops: VecDeque<SimOp>,
     state: SimulationState,
 }
 
-impl<OpIt> Simulation<OpIt>
-where
-    OpIt: Iterator<Item = SimOp>,
-{
-    fn step_until(&mut self, mut cond: impl FnMut(&SimulationState) -> bool) -> Result<(), ()> {
+impl Simulation {
+    fn new(program: Vec<Instruction>, crt_config: CrtConfig) -> Self {
+        Simulation {
+            program,
+            pending_ops: VecDeque::new(),
+            state: SimulationState::new(crt_config),
+        }
+    }
+
+    fn step_until(
+        &mut self,
+        mut cond: impl FnMut(&SimulationState) -> bool,
+    ) -> Result<(), SimError> {
         while !cond(&self.state) {
             self.step_one()?;
         }
@@ -50,19 +440,28 @@ where
         Ok(())
     }
 
-    fn step_remaining(&mut self) {
-        while self.step_one().is_ok() {}
+    // runs until the CRT has drawn a whole frame
+    fn step_frame(&mut self) -> Result<(), SimError> {
+        let frames_drawn = self.state.crt_frames_drawn;
+        self.step_until(|st| st.crt_frames_drawn > frames_drawn)
     }
 
-    fn step_one(&mut self) -> Result<(), ()> {
-        match self.ops_iter.next() {
-            None => return Err(()),
-            Some(x) => {
-                self.state.update(x);
-            }
+    fn step_one(&mut self) -> Result<(), SimError> {
+        if self.pending_ops.is_empty() {
+            let instr = usize::try_from(self.state.program_counter)
+                .ok()
+                .and_then(|pc| self.program.get(pc))
+                .ok_or(SimError::ProgramEnded)?;
+
+            self.pending_ops.extend(instr.to_simulation_ops());
+            self.state.program_counter += 1;
         }
 
-        Ok(())
+        let op = self
+            .pending_ops
+            .pop_front()
+            .expect("instructions take at least one cycle");
+        self.state.update(op)
     }
 
     fn state(&self) -> &SimulationState {
@@ -70,81 +469,262 @@ where
     }
 }
 
-const CRT_COLUMNS: usize = 40;
-const CRT_ROWS: usize = 6;
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct CrtConfig {
+    columns: usize,
+    rows: usize,
+    // pixels covered by the sprite, centered on register X
+    sprite_width: usize,
+}
+
+impl Default for CrtConfig {
+    fn default() -> Self {
+        CrtConfig {
+            columns: 40,
+            rows: 6,
+            sprite_width: 3,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Framebuffer {
+    columns: usize,
+    rows: usize,
+    pixels: Vec<bool>,
+}
+
+impl Framebuffer {
+    fn new(columns: usize, rows: usize) -> Self {
+        Framebuffer {
+            columns,
+            rows,
+            pixels: vec![false; columns * rows],
+        }
+    }
+
+    // Reads an image drawn with `#` and `.`, as printed by `render`
+    fn parse(input: &str, crt_config: CrtConfig) -> Result<Self, AssembleError> {
+        let lines = input.lines().filter(|l| !l.is_empty()).collect::<Vec<_>>();
+        if lines.len() != crt_config.rows {
+            return Err(AssembleError::InvalidImage(format!(
+                "expected {} rows, found {}",
+                crt_config.rows,
+                lines.len()
+            )));
+        }
+
+        let mut image = Framebuffer::new(crt_config.columns, crt_config.rows);
+        for (row, line) in lines.into_iter().enumerate() {
+            if line.len() != crt_config.columns {
+                return Err(AssembleError::InvalidImage(format!(
+                    "expected {} columns in row {row}, found {}",
+                    crt_config.columns,
+                    line.len()
+                )));
+            }
+
+            for (column, c) in line.chars().enumerate() {
+                image.pixels[row * crt_config.columns + column] = match c {
+                    '#' => true,
+                    '.' => false,
+                    _ => {
+                        return Err(AssembleError::InvalidImage(format!(
+                            "invalid pixel {c:?} at row {row}, column {column}"
+                        )))
+                    }
+                };
+            }
+        }
+
+        Ok(image)
+    }
+
+    fn lines(&self) -> impl Iterator<Item = &[bool]> {
+        self.pixels.chunks_exact(self.columns)
+    }
+
+    fn render(&self) -> String {
+        self.render_with(|is_lit| if is_lit { "#" } else { "." }, "")
+    }
+
+    // Plain (ASCII) PBM, lit pixels are black as in the text rendering
+    fn to_pbm(&self) -> String {
+        let header = format!("P1\n{} {}\n", self.columns, self.rows);
+        header + &self.render_with(|is_lit| if is_lit { "1" } else { "0" }, " ")
+    }
+
+    // Plain (ASCII) PGM, lit pixels are black as in the text rendering
+    fn to_pgm(&self) -> String {
+        let header = format!("P2\n{} {}\n255\n", self.columns, self.rows);
+        header + &self.render_with(|is_lit| if is_lit { "0" } else { "255" }, " ")
+    }
+
+    fn render_with(&self, pixel: impl Fn(bool) -> &'static str, separator: &str) -> String {
+        self.lines()
+            .map(|line| {
+                let line = line.iter().map(|is_lit| pixel(*is_lit)).collect::<Vec<_>>();
+                line.join(separator) + "\n"
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Register {
+    W,
+    X,
+    Y,
+    Z,
+}
+
+impl Register {
+    const ALL: [Register; 4] = [Register::W, Register::X, Register::Y, Register::Z];
+
+    fn name(self) -> char {
+        match self {
+            Register::W => 'w',
+            Register::X => 'x',
+            Register::Y => 'y',
+            Register::Z => 'z',
+        }
+    }
+}
 
 #[derive(Debug, PartialEq)]
 struct SimulationState {
-    register_x: i64,
+    registers: [i64; Register::ALL.len()],
+    // index of the next instruction to fetch
+    program_counter: i64,
     started_cycle_counter: usize,
 
-    crt: [bool; CRT_ROWS * CRT_COLUMNS],
+    crt: Framebuffer,
     crt_electron_gun_pos: usize,
+    crt_frames_drawn: usize,
+    sprite_width: usize,
 }
 
 impl SimulationState {
-    fn update(&mut self, op: SimOp) {
+    fn new(crt_config: CrtConfig) -> Self {
+        let mut registers = [0; Register::ALL.len()];
+        registers[Register::X as usize] = 1;
+
+        SimulationState {
+            registers,
+            program_counter: 0,
+            started_cycle_counter: 0,
+
+            crt: Framebuffer::new(crt_config.columns, crt_config.rows),
+            crt_electron_gun_pos: 0,
+            crt_frames_drawn: 0,
+            sprite_width: crt_config.sprite_width,
+        }
+    }
+
+    fn register(&self, reg: Register) -> i64 {
+        self.registers[reg as usize]
+    }
+
+    fn update(&mut self, op: SimOp) -> Result<(), SimError> {
+        // the program counter already moved past the jump instruction; a jump far
+        // enough to saturate lands outside the program either way
+        let jump = |pc: i64, offset: i64| pc.saturating_add(offset).saturating_sub(1);
+
         match op {
             SimOp::IncrStartedCycleCounter => {
                 self.started_cycle_counter += 1;
             }
-            SimOp::AddX(amount) => {
-                self.register_x += amount;
+            SimOp::Add(reg, amount) => {
+                let value = &mut self.registers[reg as usize];
+                *value = value.checked_add(amount).ok_or(SimError::Overflow(reg))?;
+            }
+            SimOp::Mul(reg, factor) => {
+                let value = &mut self.registers[reg as usize];
+                *value = value.checked_mul(factor).ok_or(SimError::Overflow(reg))?;
+            }
+            SimOp::Jump(offset) => {
+                self.program_counter = jump(self.program_counter, offset);
+            }
+            SimOp::JumpIfNonZero(reg, offset) => {
+                if self.register(reg) != 0 {
+                    self.program_counter = jump(self.program_counter, offset);
+                }
             }
             SimOp::UpdateCrt => {
-                let gun_column = (self.crt_electron_gun_pos % CRT_COLUMNS) as i64;
-                let sprite_pos = (self.register_x - 1)..=(self.register_x + 1);
+                let gun_column = (self.crt_electron_gun_pos % self.crt.columns) as i64;
+                let sprite_start = self.register(Register::X) - (self.sprite_width as i64 - 1) / 2;
+                let sprite_pos = sprite_start..(sprite_start + self.sprite_width as i64);
                 let is_lit = sprite_pos.contains(&gun_column);
-                self.crt[self.crt_electron_gun_pos] = is_lit;
+                self.crt.pixels[self.crt_electron_gun_pos] = is_lit;
 
-                self.crt_electron_gun_pos =
-                    (self.crt_electron_gun_pos + 1) % (CRT_ROWS * CRT_COLUMNS);
+                self.crt_electron_gun_pos = (self.crt_electron_gun_pos + 1) % self.crt.pixels.len();
+                if self.crt_electron_gun_pos == 0 {
+                    self.crt_frames_drawn += 1;
+                }
             }
         }
-    }
-}
 
-impl Default for SimulationState {
-    fn default() -> Self {
-        SimulationState {
-            register_x: 1,
-            started_cycle_counter: 0,
-
-            crt: [false; CRT_ROWS * CRT_COLUMNS],
-            crt_electron_gun_pos: 0,
-        }
+        Ok(())
     }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 enum SimOp {
-    AddX(i64),
+    Add(Register, i64),
+    Mul(Register, i64),
+    Jump(i64),
+    JumpIfNonZero(Register, i64),
     IncrStartedCycleCounter,
     UpdateCrt,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 enum Instruction {
-    AddX(i64),
     Noop,
+    Add(Register, i64),
+    Mul(Register, i64),
+    // jumps are relative to the jump instruction itself
+    Jump(i64),
+    JumpIfNonZero(Register, i64),
 }
 
 impl Instruction {
-    fn to_simulation_ops(self) -> Box<dyn Iterator<Item = SimOp>> {
+    fn cycles(self) -> usize {
         match self {
-            Instruction::AddX(amount) => Box::new(
-                [
-                    SimOp::IncrStartedCycleCounter,
-                    SimOp::UpdateCrt,
-                    SimOp::IncrStartedCycleCounter,
-                    SimOp::UpdateCrt,
-                    SimOp::AddX(amount),
-                ]
-                .into_iter(),
-            ),
-            Instruction::Noop => {
-                Box::new([SimOp::IncrStartedCycleCounter, SimOp::UpdateCrt].into_iter())
-            }
+            Instruction::Noop | Instruction::Jump(_) | Instruction::JumpIfNonZero(..) => 1,
+            Instruction::Add(..) | Instruction::Mul(..) => 2,
+        }
+    }
+
+    // applied once all of the instruction's cycles are done
+    fn effect(self) -> Option<SimOp> {
+        match self {
+            Instruction::Noop => None,
+            Instruction::Add(reg, amount) => Some(SimOp::Add(reg, amount)),
+            Instruction::Mul(reg, factor) => Some(SimOp::Mul(reg, factor)),
+            Instruction::Jump(offset) => Some(SimOp::Jump(offset)),
+            Instruction::JumpIfNonZero(reg, offset) => Some(SimOp::JumpIfNonZero(reg, offset)),
+        }
+    }
+
+    fn to_simulation_ops(self) -> impl Iterator<Item = SimOp> {
+        std::iter::repeat_n(
+            [SimOp::IncrStartedCycleCounter, SimOp::UpdateCrt],
+            self.cycles(),
+        )
+        .flatten()
+        .chain(self.effect())
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Noop => write!(f, "noop"),
+            Instruction::Add(reg, amount) => write!(f, "add{} {amount}", reg.name()),
+            Instruction::Mul(reg, factor) => write!(f, "mul{} {factor}", reg.name()),
+            Instruction::Jump(offset) => write!(f, "jmp {offset}"),
+            Instruction::JumpIfNonZero(reg, offset) => write!(f, "jnz {} {offset}", reg.name()),
         }
     }
 }
@@ -154,16 +734,34 @@ impl FromStr for Instruction {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut tokens = s.split_ascii_whitespace();
+        let opcode = tokens.next().ok_or(ParseErr)?;
 
-        let instr = match tokens.next().ok_or(ParseErr)? {
-            "noop" => Instruction::Noop,
-            "addx" => {
-                let amount = tokens.next().ok_or(ParseErr)?;
-                let amount = amount.parse().map_err(|_| ParseErr)?;
-
-                Instruction::AddX(amount)
+        let mut operand = || tokens.next().ok_or(ParseErr);
+        let parse_register = |name: &str| {
+            let mut name = name.chars();
+            match (name.next(), name.next()) {
+                (Some(c), None) => Register::ALL.into_iter().find(|reg| reg.name() == c),
+                _ => None,
             }
-            _ => return Err(ParseErr),
+        };
+        let parse_number = |n: &str| n.parse::<i64>().map_err(|_| ParseErr);
+
+        // register-specific opcodes end in the register's name, as in `addx`
+        let register_suffix = |prefix| opcode.strip_prefix(prefix).and_then(parse_register);
+
+        let instr = if opcode == "noop" {
+            Instruction::Noop
+        } else if opcode == "jmp" {
+            Instruction::Jump(parse_number(operand()?)?)
+        } else if opcode == "jnz" {
+            let reg = parse_register(operand()?).ok_or(ParseErr)?;
+            Instruction::JumpIfNonZero(reg, parse_number(operand()?)?)
+        } else if let Some(reg) = register_suffix("add") {
+            Instruction::Add(reg, parse_number(operand()?)?)
+        } else if let Some(reg) = register_suffix("mul") {
+            Instruction::Mul(reg, parse_number(operand()?)?)
+        } else {
+            return Err(ParseErr);
         };
 
         Ok(instr)
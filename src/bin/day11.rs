@@ -1,54 +1,413 @@
 use nom::{branch, bytes::complete::tag, combinator, multi, sequence, IResult};
-use std::{cell::RefCell, io::Read};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fmt,
+    io::Read,
+    ops::{Add, Mul, Neg, Sub},
+};
 
 fn main() {
     let mut input = String::new();
     std::io::stdin().lock().read_to_string(&mut input).unwrap();
 
-    let mut monkeys = parse_monkeys(&input)
-        .unwrap()
-        .1
-        .into_iter()
-        .map(RefCell::new)
-        .collect::<Vec<_>>();
+    let mut trace_items = false;
+    let mut report_rounds = Vec::new();
+    let mut trace_part = 1;
+    let mut part2_rounds = 10000;
+    let mut cycle_solver = false;
+    let mut check = false;
+    let mut check_reduction_rounds = None;
 
-    for _round in 0..20 {
-        for monkey in &monkeys {
-            for MonkeyThrow {
-                dest_monkey,
-                item_worry,
-            } in monkey.borrow_mut().step()
-            {
-                monkeys[dest_monkey]
-                    .borrow_mut()
-                    .item_worry_levels
-                    .push(item_worry);
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--trace-items" => trace_items = true,
+            // which part --trace-items and --report-rounds apply to
+            "--trace-part" => {
+                trace_part = match args.next().as_deref() {
+                    Some("1") => 1,
+                    Some("2") => 2,
+                    _ => panic!("--trace-part must be 1 or 2"),
+                }
+            }
+            "--report-rounds" => {
+                report_rounds = args
+                    .next()
+                    .expect("missing report rounds")
+                    .split(',')
+                    .map(|r| r.parse().expect("invalid round number"))
+                    .collect()
+            }
+            "--part2-rounds" => {
+                part2_rounds = args
+                    .next()
+                    .and_then(|r| r.parse().ok())
+                    .expect("invalid round count")
+            }
+            "--cycle-solver" => cycle_solver = true,
+            // the check compares the cycle solver against plain simulation, so it needs the former
+            "--check" => {
+                check = true;
+                cycle_solver = true;
             }
+            "--check-reduction" => {
+                check_reduction_rounds = Some(
+                    args.next()
+                        .and_then(|r| r.parse().ok())
+                        .expect("invalid round count"),
+                )
+            }
+            _ => panic!("unknown argument: {arg}"),
         }
     }
 
-    monkeys.sort_by_key(|m| m.borrow().inspection_count);
+    let monkeys = parse_monkeys(&input).unwrap().1;
 
-    println!(
-        "{}",
-        monkeys
+    if let Some(rounds) = check_reduction_rounds {
+        match check_worry_reduction(&monkeys, rounds) {
+            Ok(()) => println!("reduced worry levels make the same throws for {rounds} rounds"),
+            Err(ThrowMismatch {
+                round,
+                monkey,
+                item_id,
+                exact_dest,
+                reduced_dest,
+            }) => panic!(
+                "reduced worry levels diverge in round {round}: monkey {monkey} throws item \
+                 {item_id} to monkey {exact_dest}, but the reduced simulation throws it to \
+                 monkey {reduced_dest}"
+            ),
+        }
+    }
+
+    let tracing = trace_items || !report_rounds.is_empty();
+    if tracing && trace_part == 2 && cycle_solver {
+        panic!("--trace-items and --report-rounds need plain simulation for part 2, not --cycle-solver or --check");
+    }
+
+    // only the traced part gets the tracing options, under a heading saying which part it is
+    let (mut part1_report_rounds, mut part2_report_rounds) = (Vec::new(), Vec::new());
+    if trace_part == 1 {
+        part1_report_rounds = report_rounds;
+    } else {
+        part2_report_rounds = report_rounds;
+    }
+    let print_heading = |part: usize| {
+        if tracing && trace_part == part {
+            println!("== Part {part} ==");
+            println!();
+        }
+    };
+
+    let part1 = SimulationConfig {
+        rounds: 20,
+        relief: |worry_level| worry_level / 3,
+        reduce_worry: false,
+        trace_items: trace_items && trace_part == 1,
+        report_rounds: part1_report_rounds,
+    };
+    print_heading(1);
+    let part1_monkeys = simulate(monkeys.clone(), &part1).unwrap_or_else(|e| panic!("{e}"));
+    println!("{}", monkey_business(inspection_counts(&part1_monkeys)));
+
+    let part2 = SimulationConfig {
+        rounds: part2_rounds,
+        relief: |worry_level| worry_level,
+        reduce_worry: true,
+        trace_items: trace_items && trace_part == 2,
+        report_rounds: part2_report_rounds,
+    };
+    print_heading(2);
+    let part2_counts = if cycle_solver {
+        let counts = simulate_item_cycles(&monkeys, &part2);
+        if check {
+            let simulated = simulate(monkeys, &part2).unwrap_or_else(|e| panic!("{e}"));
+            assert_eq!(counts, inspection_counts(&simulated));
+        }
+        counts
+    } else {
+        inspection_counts(&simulate(monkeys, &part2).unwrap_or_else(|e| panic!("{e}")))
+    };
+    println!("{}", monkey_business(part2_counts));
+}
+
+struct SimulationConfig<R> {
+    rounds: usize,
+    // applied to the worry level after each inspection
+    relief: R,
+    // keep worry levels modulo the product of all test moduli, which preserves every
+    // throw decision as long as `relief` leaves them unchanged
+    reduce_worry: bool,
+
+    // print every throw as it happens
+    trace_items: bool,
+    // print what each monkey holds and has inspected after these rounds
+    report_rounds: Vec<usize>,
+}
+
+fn simulate<R>(
+    mut monkeys: Vec<Monkey>,
+    config: &SimulationConfig<R>,
+) -> Result<Vec<Monkey>, WorryOverflow>
+where
+    R: Fn(u64) -> u64,
+{
+    let worry_modulus = monkeys
+        .iter()
+        .map(|m| m.decision_rule.test_modulus)
+        .product::<u64>();
+
+    for round in 1..=config.rounds {
+        for i in 0..monkeys.len() {
+            let throws = monkeys[i]
+                .step(&config.relief, worry_modulus, config.reduce_worry)
+                .map_err(|item| WorryOverflow {
+                    round,
+                    monkey: i,
+                    item_id: item.id,
+                    worry: item.worry,
+                })?;
+
+            for MonkeyThrow { dest_monkey, item } in throws {
+                if config.trace_items {
+                    println!(
+                        "Round {round}: monkey {i} throws item {} with worry level {} to monkey {dest_monkey}",
+                        item.id, item.worry
+                    );
+                }
+
+                monkeys[dest_monkey].items.push(item);
+            }
+        }
+
+        if config.report_rounds.contains(&round) {
+            print_round_report(round, &monkeys);
+        }
+    }
+
+    Ok(monkeys)
+}
+
+#[derive(Debug)]
+struct WorryOverflow {
+    round: usize,
+    monkey: usize,
+    item_id: usize,
+    // worry level before the inspection that overflowed
+    worry: u64,
+}
+
+impl fmt::Display for WorryOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let WorryOverflow {
+            round,
+            monkey,
+            item_id,
+            worry,
+        } = self;
+        write!(
+            f,
+            "worry level of item {item_id} overflows when monkey {monkey} inspects it in round \
+             {round} (worry level {worry})"
+        )
+    }
+}
+
+fn print_round_report(round: usize, monkeys: &[Monkey]) {
+    println!("After round {round}, the monkeys are holding items with these worry levels:");
+    for (i, monkey) in monkeys.iter().enumerate() {
+        let worry_levels = monkey
+            .items
             .iter()
-            .rev()
-            .take(2)
-            .map(|m| m.borrow().inspection_count)
-            .product::<usize>()
+            .map(|item| item.worry.to_string())
+            .collect::<Vec<_>>();
+        println!("Monkey {i}: {}", worry_levels.join(", "));
+    }
+    println!();
+
+    println!("== After round {round} ==");
+    for (i, monkey) in monkeys.iter().enumerate() {
+        println!(
+            "Monkey {i} inspected items {} times.",
+            monkey.inspection_count
+        );
+    }
+    println!();
+}
+
+// Items never interact, and with reduced worry levels each one only has finitely many
+// (monkey, worry level) states at the start of a round. So every item is followed on its
+// own until its state repeats, and its inspection counts are extrapolated from that cycle.
+fn simulate_item_cycles<R>(monkeys: &[Monkey], config: &SimulationConfig<R>) -> Vec<usize>
+where
+    R: Fn(u64) -> u64,
+{
+    assert!(
+        config.reduce_worry,
+        "items can only cycle with reduced worry levels"
     );
+
+    let worry_modulus = monkeys
+        .iter()
+        .map(|m| m.decision_rule.test_modulus)
+        .product::<u64>();
+
+    let mut total_counts = vec![0; monkeys.len()];
+
+    let starting_items = monkeys
+        .iter()
+        .enumerate()
+        .flat_map(|(i, m)| m.items.iter().map(move |item| (i, item.worry)));
+
+    for mut state in starting_items {
+        // round at which each state was first seen, and inspection counts after each round
+        let mut seen = HashMap::new();
+        let mut cumulative_counts = vec![vec![0; monkeys.len()]];
+
+        let counts: Vec<usize> = loop {
+            let round = cumulative_counts.len() - 1;
+            if round == config.rounds {
+                break cumulative_counts.pop().unwrap();
+            }
+
+            if let Some(&cycle_start) = seen.get(&state) {
+                let cycle_len = round - cycle_start;
+                let remaining = config.rounds - round;
+                let (n_cycles, leftover) = (remaining / cycle_len, remaining % cycle_len);
+
+                let at = |r: usize, m: usize| cumulative_counts[r][m];
+                break (0..monkeys.len())
+                    .map(|m| {
+                        at(round, m)
+                            + n_cycles * (at(round, m) - at(cycle_start, m))
+                            + (at(cycle_start + leftover, m) - at(cycle_start, m))
+                    })
+                    .collect();
+            }
+            seen.insert(state, round);
+
+            let mut counts = cumulative_counts[round].clone();
+            state = item_round(monkeys, state, &config.relief, worry_modulus, &mut counts);
+            cumulative_counts.push(counts);
+        };
+
+        for (total, count) in total_counts.iter_mut().zip(counts) {
+            *total += count;
+        }
+    }
+
+    total_counts
+}
+
+#[derive(Debug)]
+struct ThrowMismatch {
+    round: usize,
+    monkey: usize,
+    item_id: usize,
+    exact_dest: usize,
+    reduced_dest: usize,
+}
+
+// Runs part 2's rules with exact (arbitrarily large) worry levels alongside the reduced
+// ones, and checks that every item is thrown to the same monkey in both. Exact worry
+// levels grow very quickly, so this is only practical for a few rounds.
+fn check_worry_reduction(monkeys: &[Monkey], rounds: usize) -> Result<(), ThrowMismatch> {
+    let worry_modulus = monkeys
+        .iter()
+        .map(|m| m.decision_rule.test_modulus)
+        .product::<u64>();
+
+    // as long as both agree, both hold the same items in the same order
+    let mut held_items = monkeys
+        .iter()
+        .map(|m| {
+            m.items
+                .iter()
+                .map(|item| (item.id, item.worry, BigInt::from(item.worry)))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    for round in 1..=rounds {
+        for (i, monkey) in monkeys.iter().enumerate() {
+            for (item_id, reduced, exact) in std::mem::take(&mut held_items[i]) {
+                let reduced = monkey.worry_update.apply_mod(reduced, worry_modulus);
+                let exact = monkey.worry_update.apply_exact(&exact);
+
+                let reduced_dest = monkey.decision_rule.apply(reduced);
+                let exact_dest = monkey.decision_rule.apply_exact(&exact);
+                if reduced_dest != exact_dest {
+                    return Err(ThrowMismatch {
+                        round,
+                        monkey: i,
+                        item_id,
+                        exact_dest,
+                        reduced_dest,
+                    });
+                }
+
+                held_items[exact_dest].push((item_id, reduced, exact));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Follows one item through a single round, returning where it starts the next one
+fn item_round(
+    monkeys: &[Monkey],
+    (mut monkey_idx, mut worry): (usize, u64),
+    relief: impl Fn(u64) -> u64,
+    worry_modulus: u64,
+    counts: &mut [usize],
+) -> (usize, u64) {
+    loop {
+        let monkey = &monkeys[monkey_idx];
+        counts[monkey_idx] += 1;
+        worry = relief(monkey.worry_update.apply_mod(worry, worry_modulus));
+
+        let dest_monkey = monkey.decision_rule.apply(worry);
+        if dest_monkey <= monkey_idx {
+            // that monkey already had its turn this round
+            return (dest_monkey, worry);
+        }
+        monkey_idx = dest_monkey;
+    }
+}
+
+fn inspection_counts(monkeys: &[Monkey]) -> Vec<usize> {
+    monkeys.iter().map(|m| m.inspection_count).collect()
+}
+
+fn monkey_business(mut inspection_counts: Vec<usize>) -> u128 {
+    inspection_counts.sort_unstable();
+
+    inspection_counts
+        .iter()
+        .rev()
+        .take(2)
+        .map(|&count| count as u128)
+        .product::<u128>()
 }
 
 #[derive(Debug)]
 struct MonkeyThrow {
     dest_monkey: usize,
-    item_worry: i64,
+    item: Item,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
+struct Item {
+    // position of the item among all starting items, in input order
+    id: usize,
+    worry: u64,
+}
+
+#[derive(Clone, Debug)]
 struct Monkey {
-    item_worry_levels: Vec<i64>,
+    items: Vec<Item>,
     worry_update: WorryUpdateExpr,
     decision_rule: MonkeyDecisionRule,
 
@@ -56,75 +415,382 @@ struct Monkey {
 }
 
 impl Monkey {
-    fn step(&mut self) -> impl Iterator<Item = MonkeyThrow> {
-        for worry_level in &mut self.item_worry_levels {
+    // Fails with the item whose new worry level doesn't fit
+    fn step(
+        &mut self,
+        relief: impl Fn(u64) -> u64,
+        worry_modulus: u64,
+        reduce_worry: bool,
+    ) -> Result<impl Iterator<Item = MonkeyThrow>, Item> {
+        for item in &mut self.items {
             self.inspection_count += 1;
-            *worry_level = self.worry_update.apply(*worry_level) / 3;
+            let new_worry = if reduce_worry {
+                self.worry_update.apply_mod(item.worry, worry_modulus)
+            } else {
+                self.worry_update
+                    .apply(item.worry, worry_modulus)
+                    .ok_or(*item)?
+            };
+            item.worry = relief(new_worry);
         }
 
-        let item_worry_levels = std::mem::take(&mut self.item_worry_levels);
+        let items = std::mem::take(&mut self.items);
 
         let decision_rule = self.decision_rule.clone();
-        item_worry_levels
-            .into_iter()
-            .map(move |worry_level| MonkeyThrow {
-                dest_monkey: decision_rule.apply(worry_level),
-                item_worry: worry_level,
-            })
+        Ok(items.into_iter().map(move |item| MonkeyThrow {
+            dest_monkey: decision_rule.apply(item.worry),
+            item,
+        }))
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 enum WorryUpdateExpr {
-    AddConst(i64),
-    MulConst(i64),
+    // fast paths for the operations found in puzzle inputs
+    AddConst(u64),
+    MulConst(u64),
     Pow2,
+    General(Expr),
 }
 
 impl WorryUpdateExpr {
-    fn apply(self, worry_level: i64) -> i64 {
+    // Exact new worry level, or `None` if it doesn't fit.
+    //
+    // Worry levels can't be negative, so a negative result is replaced by its remainder
+    // modulo the product of all test moduli. That keeps every throw decision the same as
+    // for the negative value, and is exactly what `apply_mod` ends up with too.
+    fn apply(&self, worry_level: u64, modulus: u64) -> Option<u64> {
         match self {
-            Self::Pow2 => worry_level.pow(2),
-            Self::MulConst(n) => worry_level * n,
-            Self::AddConst(n) => worry_level + n,
+            Self::Pow2 => worry_level.checked_pow(2),
+            Self::MulConst(n) => worry_level.checked_mul(*n),
+            Self::AddConst(n) => worry_level.checked_add(*n),
+            Self::General(expr) => {
+                let new_worry = expr.eval(i64::try_from(worry_level).ok()?)?;
+                if new_worry < 0 {
+                    Some((new_worry as i128).rem_euclid(modulus as i128) as u64)
+                } else {
+                    Some(new_worry as u64)
+                }
+            }
+        }
+    }
+
+    // New worry level modulo `modulus`, which never overflows
+    fn apply_mod(&self, worry_level: u64, modulus: u64) -> u64 {
+        let (worry_level, modulus_wide) = (worry_level as u128, modulus as u128);
+        match self {
+            Self::Pow2 => (worry_level.pow(2) % modulus_wide) as u64,
+            Self::MulConst(n) => (worry_level * *n as u128 % modulus_wide) as u64,
+            Self::AddConst(n) => ((worry_level + *n as u128) % modulus_wide) as u64,
+            Self::General(expr) => expr.eval_mod(worry_level as i64, modulus as i64) as u64,
+        }
+    }
+}
+
+impl WorryUpdateExpr {
+    fn apply_exact(&self, worry_level: &BigInt) -> BigInt {
+        let worry_level = worry_level.clone();
+        match self {
+            Self::Pow2 => worry_level.clone() * worry_level,
+            Self::MulConst(n) => worry_level * BigInt::from(*n),
+            Self::AddConst(n) => worry_level + BigInt::from(*n),
+            Self::General(expr) => expr.eval_exact(&worry_level),
         }
     }
 }
 
+impl From<Expr> for WorryUpdateExpr {
+    fn from(expr: Expr) -> Self {
+        use Expr::*;
+
+        let as_const = |e: &Expr| match e {
+            Const(n) => u64::try_from(*n).ok(),
+            _ => None,
+        };
+
+        match &expr {
+            Mul(a, b) if matches!((&**a, &**b), (Old, Old)) => WorryUpdateExpr::Pow2,
+            Mul(a, b) | Add(a, b) if matches!(**a, Old) || matches!(**b, Old) => {
+                let other = if matches!(**a, Old) { &**b } else { &**a };
+                match (as_const(other), &expr) {
+                    (Some(n), Mul(..)) => WorryUpdateExpr::MulConst(n),
+                    (Some(n), Add(..)) => WorryUpdateExpr::AddConst(n),
+                    _ => WorryUpdateExpr::General(expr),
+                }
+            }
+            _ => WorryUpdateExpr::General(expr),
+        }
+    }
+}
+
+// Arithmetic over the old worry level and integer constants
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Expr {
+    Old,
+    Const(i64),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    // `None` on overflow
+    fn eval(&self, old: i64) -> Option<i64> {
+        match self {
+            Expr::Old => Some(old),
+            Expr::Const(n) => Some(*n),
+            Expr::Add(a, b) => a.eval(old)?.checked_add(b.eval(old)?),
+            Expr::Sub(a, b) => a.eval(old)?.checked_sub(b.eval(old)?),
+            Expr::Mul(a, b) => a.eval(old)?.checked_mul(b.eval(old)?),
+        }
+    }
+
+    // Same as `eval`, but reduced to `0..modulus` at every step so it never overflows
+    fn eval_mod(&self, old: i64, modulus: i64) -> i64 {
+        let modulus_wide = modulus as i128;
+        let reduce = |n: i128| n.rem_euclid(modulus_wide) as i64;
+
+        match self {
+            Expr::Old => reduce(old as i128),
+            Expr::Const(n) => reduce(*n as i128),
+            Expr::Add(a, b) => {
+                reduce(a.eval_mod(old, modulus) as i128 + b.eval_mod(old, modulus) as i128)
+            }
+            Expr::Sub(a, b) => {
+                reduce(a.eval_mod(old, modulus) as i128 - b.eval_mod(old, modulus) as i128)
+            }
+            Expr::Mul(a, b) => {
+                reduce(a.eval_mod(old, modulus) as i128 * b.eval_mod(old, modulus) as i128)
+            }
+        }
+    }
+
+    fn eval_exact(&self, old: &BigInt) -> BigInt {
+        match self {
+            Expr::Old => old.clone(),
+            Expr::Const(n) => BigInt::from(*n),
+            Expr::Add(a, b) => a.eval_exact(old) + b.eval_exact(old),
+            Expr::Sub(a, b) => a.eval_exact(old) - b.eval_exact(old),
+            Expr::Mul(a, b) => a.eval_exact(old) * b.eval_exact(old),
+        }
+    }
+}
+
+// Just enough of an arbitrary-precision integer to run monkey operations exactly.
+// Magnitude is stored as little-endian base 2^32 digits, without trailing zeros.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct BigInt {
+    negative: bool,
+    digits: Vec<u32>,
+}
+
+impl BigInt {
+    fn new(negative: bool, mut digits: Vec<u32>) -> Self {
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+
+        BigInt {
+            negative: negative && !digits.is_empty(),
+            digits,
+        }
+    }
+
+    // Euclidean remainder, always in `0..modulus`
+    fn rem_euclid_u64(&self, modulus: u64) -> u64 {
+        let rem = self.digits.iter().rev().fold(0, |rem, &digit| {
+            (((rem as u128) << 32 | digit as u128) % modulus as u128) as u64
+        });
+
+        if self.negative && rem != 0 {
+            modulus - rem
+        } else {
+            rem
+        }
+    }
+
+    fn cmp_magnitude(a: &[u32], b: &[u32]) -> Ordering {
+        a.len()
+            .cmp(&b.len())
+            .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+    }
+
+    fn add_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+        let mut res = Vec::with_capacity(a.len().max(b.len()) + 1);
+        let mut carry = 0;
+        for i in 0..a.len().max(b.len()) {
+            let sum = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+            res.push(sum as u32);
+            carry = sum >> 32;
+        }
+        res.push(carry as u32);
+        res
+    }
+
+    // requires |a| >= |b|
+    fn sub_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+        let mut res = Vec::with_capacity(a.len());
+        let mut borrow = 0;
+        for (i, &digit) in a.iter().enumerate() {
+            let diff = digit as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+            res.push(diff.rem_euclid(1 << 32) as u32);
+            borrow = (diff < 0) as i64;
+        }
+        res
+    }
+}
+
+impl From<u64> for BigInt {
+    fn from(n: u64) -> Self {
+        BigInt::new(false, vec![n as u32, (n >> 32) as u32])
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(n: i64) -> Self {
+        let magnitude = BigInt::from(n.unsigned_abs());
+        BigInt::new(n < 0, magnitude.digits)
+    }
+}
+
+impl Add for BigInt {
+    type Output = BigInt;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        if self.negative == rhs.negative {
+            return BigInt::new(
+                self.negative,
+                BigInt::add_magnitude(&self.digits, &rhs.digits),
+            );
+        }
+
+        match BigInt::cmp_magnitude(&self.digits, &rhs.digits) {
+            Ordering::Less => BigInt::new(
+                rhs.negative,
+                BigInt::sub_magnitude(&rhs.digits, &self.digits),
+            ),
+            _ => BigInt::new(
+                self.negative,
+                BigInt::sub_magnitude(&self.digits, &rhs.digits),
+            ),
+        }
+    }
+}
+
+impl Neg for BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> Self::Output {
+        BigInt::new(!self.negative, self.digits)
+    }
+}
+
+impl Sub for BigInt {
+    type Output = BigInt;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.add(rhs.neg())
+    }
+}
+
+impl Mul for BigInt {
+    type Output = BigInt;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        let mut res = vec![0u32; self.digits.len() + rhs.digits.len()];
+        for (i, &a) in self.digits.iter().enumerate() {
+            let mut carry = 0;
+            for (j, &b) in rhs.digits.iter().enumerate() {
+                let prod = a as u64 * b as u64 + res[i + j] as u64 + carry;
+                res[i + j] = prod as u32;
+                carry = prod >> 32;
+            }
+            res[i + rhs.digits.len()] = carry as u32;
+        }
+
+        BigInt::new(self.negative != rhs.negative, res)
+    }
+}
+
 #[derive(Clone, Debug)]
 struct MonkeyDecisionRule {
-    test_modulus: i64,
+    test_modulus: u64,
     monkey_if_true: usize,
     monkey_if_false: usize,
 }
 
 impl MonkeyDecisionRule {
-    fn apply(&self, worry_level: i64) -> usize {
-        if worry_level % self.test_modulus == 0 {
+    fn apply(&self, worry_level: u64) -> usize {
+        if worry_level.is_multiple_of(self.test_modulus) {
             self.monkey_if_true
         } else {
             self.monkey_if_false
         }
     }
+
+    fn apply_exact(&self, worry_level: &BigInt) -> usize {
+        self.apply(worry_level.rem_euclid_u64(self.test_modulus))
+    }
 }
 
 fn parse_worry_update_expr(input: &str) -> IResult<&str, WorryUpdateExpr> {
-    sequence::preceded(
-        tag("new = old "),
-        branch::alt((
-            combinator::map(tag("* old"), |_| WorryUpdateExpr::Pow2),
-            combinator::map(
-                sequence::preceded(tag("* "), nom::character::complete::i64),
-                WorryUpdateExpr::MulConst,
-            ),
-            combinator::map(
-                sequence::preceded(tag("+ "), nom::character::complete::i64),
-                WorryUpdateExpr::AddConst,
-            ),
-        )),
+    combinator::map(
+        sequence::preceded(tag("new = "), parse_expr),
+        WorryUpdateExpr::from,
     )(input)
 }
 
+// expr := term (('+' | '-') term)*
+fn parse_expr(input: &str) -> IResult<&str, Expr> {
+    let (input, first) = parse_term(input)?;
+
+    multi::fold_many0(
+        sequence::pair(
+            parse_operator(branch::alt((tag("+"), tag("-")))),
+            parse_term,
+        ),
+        move || first.clone(),
+        |lhs, (op, rhs)| match op {
+            "+" => Expr::Add(Box::new(lhs), Box::new(rhs)),
+            _ => Expr::Sub(Box::new(lhs), Box::new(rhs)),
+        },
+    )(input)
+}
+
+// term := atom ('*' atom)*
+fn parse_term(input: &str) -> IResult<&str, Expr> {
+    let (input, first) = parse_atom(input)?;
+
+    multi::fold_many0(
+        sequence::preceded(parse_operator(tag("*")), parse_atom),
+        move || first.clone(),
+        |lhs, rhs| Expr::Mul(Box::new(lhs), Box::new(rhs)),
+    )(input)
+}
+
+// atom := 'old' | integer | '(' expr ')'
+fn parse_atom(input: &str) -> IResult<&str, Expr> {
+    branch::alt((
+        combinator::map(tag("old"), |_| Expr::Old),
+        combinator::map(nom::character::complete::i64, Expr::Const),
+        sequence::delimited(
+            parse_operator(tag("(")),
+            parse_expr,
+            parse_operator(tag(")")),
+        ),
+    ))(input)
+}
+
+fn parse_operator<'s>(
+    op: impl FnMut(&'s str) -> IResult<&'s str, &'s str>,
+) -> impl FnMut(&'s str) -> IResult<&'s str, &'s str> {
+    sequence::delimited(
+        nom::character::complete::space0,
+        op,
+        nom::character::complete::space0,
+    )
+}
+
 fn parse_monkey_decision_rule(input: &str) -> IResult<&str, MonkeyDecisionRule> {
     combinator::map(
         sequence::tuple((
@@ -133,7 +799,7 @@ fn parse_monkey_decision_rule(input: &str) -> IResult<&str, MonkeyDecisionRule>
                     nom::character::complete::multispace1,
                     tag("Test: divisible by "),
                 ),
-                nom::character::complete::i64,
+                nom::character::complete::u64,
                 tag("\n"),
             ),
             sequence::delimited(
@@ -162,7 +828,7 @@ fn parse_monkey_decision_rule(input: &str) -> IResult<&str, MonkeyDecisionRule>
 }
 
 fn parse_monkey(input: &str) -> IResult<&str, Monkey> {
-    let parse_starting_items = multi::separated_list1(tag(", "), nom::character::complete::i64);
+    let parse_starting_items = multi::separated_list1(tag(", "), nom::character::complete::u64);
 
     sequence::delimited(
         sequence::tuple((tag("Monkey "), nom::character::complete::digit1, tag(":\n"))),
@@ -173,7 +839,10 @@ fn parse_monkey(input: &str) -> IResult<&str, Monkey> {
                 parse_monkey_decision_rule,
             )),
             |(item_worry_levels, worry_update, decision_rule)| Monkey {
-                item_worry_levels,
+                items: item_worry_levels
+                    .into_iter()
+                    .map(|worry| Item { id: 0, worry })
+                    .collect(),
                 worry_update,
                 decision_rule,
                 inspection_count: 0,
@@ -184,5 +853,12 @@ fn parse_monkey(input: &str) -> IResult<&str, Monkey> {
 }
 
 fn parse_monkeys(input: &str) -> IResult<&str, Vec<Monkey>> {
-    multi::many1(parse_monkey)(input)
+    combinator::map(multi::many1(parse_monkey), |mut monkeys| {
+        let items = monkeys.iter_mut().flat_map(|m| m.items.iter_mut());
+        for (id, item) in items.enumerate() {
+            item.id = id;
+        }
+
+        monkeys
+    })(input)
 }
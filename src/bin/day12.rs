@@ -1,15 +1,152 @@
-use std::{collections::VecDeque, io::stdin};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, VecDeque},
+    fmt,
+    io::stdin,
+};
 
 fn main() {
+    let mut show_paths = false;
+    let mut export_distances = None;
+    let mut distance_queries = Vec::new();
+    let mut rules = MovementRules::default();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut next_number = || -> i8 {
+            args.next()
+                .and_then(|n| n.parse().ok())
+                .unwrap_or_else(|| panic!("{arg} requires a number"))
+        };
+
+        match arg.as_str() {
+            "--show-paths" => show_paths = true,
+            "--max-climb" => rules.max_climb = next_number(),
+            "--max-descent" => rules.max_descent = next_number(),
+            "--elevation-cost" => {
+                // capped so that summing step costs along a path can't overflow
+                rules.elevation_cost = args
+                    .next()
+                    .and_then(|n| n.parse::<u32>().ok())
+                    .unwrap_or_else(|| panic!("{arg} requires a number from 0 to {}", u32::MAX))
+                    as usize
+            }
+            "--diagonal" => rules.diagonal = true,
+            "--export-distances" => {
+                export_distances = Some(args.next().expect("missing distance export format"))
+            }
+            "--distance-from" => {
+                let pos = args.next().expect("missing start cell");
+                let (x, y) = pos.split_once(',').expect("start cell must be row,column");
+                distance_queries.push((
+                    x.parse::<usize>().expect("invalid start row"),
+                    y.parse::<usize>().expect("invalid start column"),
+                ));
+            }
+            _ => panic!("unknown argument: {arg}"),
+        }
+    }
+
     let puzzle = Puzzle::parse(
         stdin()
             .lines()
             .map(Result::unwrap)
             .take_while(|s| !s.is_empty()),
-    );
+    )
+    .unwrap_or_else(|e| panic!("invalid input: {e}"));
+
+    let routes = [
+        ("S", puzzle.solve_part1(&rules)),
+        ("any 'a' cell", puzzle.solve_part2(&rules)),
+    ];
+    for (from, route) in routes {
+        let Some((cost, path)) = route else {
+            println!("no path from {from} to E");
+            continue;
+        };
+
+        println!("{cost}");
+
+        if show_paths {
+            println!("{}", puzzle.render_path(&path));
+        }
+    }
+
+    if export_distances.is_some() || !distance_queries.is_empty() {
+        let field = puzzle.distance_field_to_end(&rules);
+
+        match export_distances.as_deref() {
+            Some("csv") => print!("{}", field.to_csv()),
+            Some("heatmap") => print!("{}", field.to_heatmap()),
+            Some(format) => panic!("unknown distance export format: {format}"),
+            None => {}
+        }
+
+        for (x, y) in distance_queries {
+            if !field.contains((x, y)) {
+                let (rows, columns) = field.size();
+                println!("{x},{y}: invalid cell, the map is {rows}x{columns}");
+                continue;
+            }
+
+            match field.distance_from((x, y)) {
+                Some(dist) => println!("{x},{y}: {dist}"),
+                None => println!("{x},{y}: no path to E"),
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct MovementRules {
+    max_climb: i8,
+    max_descent: i8,
+    diagonal: bool,
+    // extra cost of a step for each unit of elevation it climbs or descends
+    elevation_cost: usize,
+}
+
+impl MovementRules {
+    fn step_cost(&self, from_height: i8, to_height: i8) -> Option<usize> {
+        let climb = to_height - from_height;
+        if climb <= self.max_climb && -climb <= self.max_descent {
+            Some(1 + self.elevation_cost * climb.unsigned_abs() as usize)
+        } else {
+            None
+        }
+    }
+
+    fn is_weighted(&self) -> bool {
+        self.elevation_cost != 0
+    }
+
+    fn directions(&self) -> &'static [(isize, isize)] {
+        if self.diagonal {
+            &[
+                (-1, 0),
+                (1, 0),
+                (0, -1),
+                (0, 1),
+                (-1, -1),
+                (-1, 1),
+                (1, -1),
+                (1, 1),
+            ]
+        } else {
+            &[(-1, 0), (1, 0), (0, -1), (0, 1)]
+        }
+    }
+}
 
-    println!("{}", puzzle.solve_part1());
-    println!("{}", puzzle.solve_part2());
+impl Default for MovementRules {
+    fn default() -> Self {
+        MovementRules {
+            max_climb: 1,
+            max_descent: i8::MAX,
+            diagonal: false,
+            elevation_cost: 0,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -20,55 +157,108 @@ struct Puzzle {
 }
 
 impl Puzzle {
-    fn parse(input: impl Iterator<Item = String>) -> Self {
-        let mut start = (0, 0);
-        let mut end = (0, 0);
-
-        let map = input
-            .enumerate()
-            .map(|(x, line)| {
-                line.bytes()
-                    .enumerate()
-                    .map(|(y, val)| match val {
-                        b'a'..=b'z' => (val - b'a') as i8,
-                        b'S' => {
-                            start = (x, y);
-                            0
-                        }
-                        b'E' => {
-                            end = (x, y);
-                            (b'z' - b'a') as i8
-                        }
-                        _ => unreachable!("invalid input"),
-                    })
-                    .collect()
-            })
-            .collect();
+    fn parse(input: impl Iterator<Item = String>) -> Result<Self, ParseError> {
+        let mut start = None;
+        let mut end = None;
+
+        let set_marker = |marker: &mut Option<(usize, usize)>, name, pos| match *marker {
+            Some(first) => Err(ParseError::DuplicateMarker {
+                marker: name,
+                first,
+                second: pos,
+            }),
+            None => {
+                *marker = Some(pos);
+                Ok(())
+            }
+        };
+
+        let mut map: Vec<Vec<i8>> = Vec::new();
+        for (x, line) in input.enumerate() {
+            let row = line
+                .chars()
+                .enumerate()
+                .map(|(y, val)| match val {
+                    'a'..='z' => Ok((val as u8 - b'a') as i8),
+                    'S' => set_marker(&mut start, 'S', (x, y)).map(|_| 0),
+                    'E' => set_marker(&mut end, 'E', (x, y)).map(|_| (b'z' - b'a') as i8),
+                    _ => Err(ParseError::InvalidCell { pos: (x, y), val }),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            if let Some(first_row) = map.first() {
+                if row.len() != first_row.len() {
+                    return Err(ParseError::RaggedRow {
+                        row: x,
+                        len: row.len(),
+                        expected_len: first_row.len(),
+                    });
+                }
+            }
+
+            map.push(row);
+        }
+
+        if map.first().is_none_or(Vec::is_empty) {
+            return Err(ParseError::Empty);
+        }
 
-        Puzzle { map, start, end }
+        Ok(Puzzle {
+            map,
+            start: start.ok_or(ParseError::MissingMarker('S'))?,
+            end: end.ok_or(ParseError::MissingMarker('E'))?,
+        })
     }
 
-    fn solve_part1(&self) -> usize {
-        let neigh_filter = |(neigh_x, neigh_y): (usize, usize), (px, py): (usize, usize)| {
-            self.map[neigh_x][neigh_y] - self.map[px][py] <= 1
+    // Draws the path over the heightmap, each step pointing to the next with `>v<^`
+    // (or `/` and `\\` for diagonal steps)
+    fn render_path(&self, path: &[(usize, usize)]) -> String {
+        let mut canvas = self
+            .map
+            .iter()
+            .map(|line| line.iter().map(|&h| (b'a' + h as u8) as char).collect())
+            .collect::<Vec<Vec<_>>>();
+
+        canvas[self.start.0][self.start.1] = 'S';
+        canvas[self.end.0][self.end.1] = 'E';
+
+        for step in path.windows(2) {
+            let ((x, y), (next_x, next_y)) = (step[0], step[1]);
+            canvas[x][y] = match (next_x as isize - x as isize, next_y as isize - y as isize) {
+                (0, 1) => '>',
+                (1, 0) => 'v',
+                (0, -1) => '<',
+                (-1, 0) => '^',
+                (-1, 1) | (1, -1) => '/',
+                (1, 1) | (-1, -1) => '\\',
+                _ => unreachable!("path steps must be to adjacent cells"),
+            };
+        }
+
+        canvas
+            .into_iter()
+            .map(|line| line.into_iter().collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn solve_part1(&self, rules: &MovementRules) -> Option<(usize, Vec<(usize, usize)>)> {
+        let step_cost = |(neigh_x, neigh_y): (usize, usize), (px, py): (usize, usize)| {
+            rules.step_cost(self.map[px][py], self.map[neigh_x][neigh_y])
         };
-        let mut bfs = PuzzleBfs::new(self, &neigh_filter);
+        let mut bfs = PuzzleBfs::new(self, rules, &step_cost);
 
         let stop_cond = |coords| coords == self.end;
         bfs.run_to_completion(self.start, stop_cond);
 
-        let (endx, endy) = self.end;
-        bfs.dist_from_start[endx][endy]
+        let field = bfs.into_distance_field(self.start);
+        let mut path = field.path_to_origin(self.end)?;
+        path.reverse();
+        Some((field.distance_from(self.end)?, path))
     }
 
-    fn solve_part2(&self) -> usize {
-        let neigh_filter = |(neigh_x, neigh_y): (usize, usize), (px, py): (usize, usize)| {
-            self.map[px][py] - self.map[neigh_x][neigh_y] <= 1
-        };
-        let mut bfs = PuzzleBfs::new(self, &neigh_filter);
-
-        let stop_cond = |_| false;
-        bfs.run_to_completion(self.end, stop_cond);
+    fn solve_part2(&self, rules: &MovementRules) -> Option<(usize, Vec<(usize, usize)>)> {
+        let field = self.distance_field_to_end(rules);
 
         let possible_start_positions = self.map.iter().enumerate().flat_map(|(x, line)| {
             line.iter()
@@ -76,24 +266,183 @@ impl Puzzle {
                 .filter_map(move |(y, &h)| if h == 0 { Some((x, y)) } else { None })
         });
 
-        possible_start_positions
-            .map(|(x, y)| bfs.dist_from_start[x][y])
-            .min()
-            .unwrap()
+        let (dist, best_start) = possible_start_positions
+            .filter_map(|pos| Some((field.distance_from(pos)?, pos)))
+            .min()?;
+
+        // the search ran backwards from the end, so this path already goes towards it
+        Some((dist, field.path_to_origin(best_start)?))
+    }
+
+    // Distance from every cell to the end, found by searching backwards from it
+    fn distance_field_to_end(&self, rules: &MovementRules) -> DistanceField {
+        // searching backwards from the end, so steps are taken in reverse
+        let step_cost = |(neigh_x, neigh_y): (usize, usize), (px, py): (usize, usize)| {
+            rules.step_cost(self.map[neigh_x][neigh_y], self.map[px][py])
+        };
+        let mut bfs = PuzzleBfs::new(self, rules, &step_cost);
+
+        let stop_cond = |_| false;
+        bfs.run_to_completion(self.end, stop_cond);
+
+        bfs.into_distance_field(self.end)
+    }
+}
+
+#[derive(Debug)]
+struct DistanceField {
+    origin: (usize, usize),
+    dist: Vec<Vec<usize>>,
+    prev: Vec<Vec<(usize, usize)>>,
+}
+
+impl DistanceField {
+    // (rows, columns)
+    fn size(&self) -> (usize, usize) {
+        (self.dist.len(), self.dist.first().map_or(0, Vec::len))
+    }
+
+    fn contains(&self, (x, y): (usize, usize)) -> bool {
+        let (rows, columns) = self.size();
+        x < rows && y < columns
+    }
+
+    fn distance_from(&self, (x, y): (usize, usize)) -> Option<usize> {
+        self.dist
+            .get(x)?
+            .get(y)
+            .copied()
+            .filter(|&dist| dist != usize::MAX)
+    }
+
+    fn path_to_origin(&self, target: (usize, usize)) -> Option<Vec<(usize, usize)>> {
+        self.distance_from(target)?;
+
+        let mut path = vec![target];
+        let mut pos = target;
+
+        while pos != self.origin {
+            pos = self.prev[pos.0][pos.1];
+            path.push(pos);
+        }
+
+        Some(path)
+    }
+
+    // One row per line, unreachable cells left empty
+    fn to_csv(&self) -> String {
+        self.dist
+            .iter()
+            .map(|line| {
+                let cells = line
+                    .iter()
+                    .map(|&d| {
+                        if d == usize::MAX {
+                            String::new()
+                        } else {
+                            d.to_string()
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                cells.join(",") + "\n"
+            })
+            .collect()
+    }
+
+    // Darker shades are further away, unreachable cells are left blank
+    fn to_heatmap(&self) -> String {
+        const SHADES: &[u8] = b".:-=+*#%@";
+
+        let max_dist = self
+            .dist
+            .iter()
+            .flatten()
+            .copied()
+            .filter(|&d| d != usize::MAX)
+            .max()
+            .unwrap_or(0)
+            .max(1);
+
+        self.dist
+            .iter()
+            .map(|line| {
+                let mut row = line
+                    .iter()
+                    .map(|&d| match d {
+                        usize::MAX => ' ',
+                        d => SHADES[d * (SHADES.len() - 1) / max_dist] as char,
+                    })
+                    .collect::<String>();
+                row.push('\n');
+                row
+            })
+            .collect()
     }
 }
 
+#[derive(Debug)]
+enum ParseError {
+    Empty,
+    InvalidCell {
+        pos: (usize, usize),
+        val: char,
+    },
+    RaggedRow {
+        row: usize,
+        len: usize,
+        expected_len: usize,
+    },
+    MissingMarker(char),
+    DuplicateMarker {
+        marker: char,
+        first: (usize, usize),
+        second: (usize, usize),
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "empty heightmap"),
+            ParseError::InvalidCell { pos: (x, y), val } => {
+                write!(f, "invalid cell {val:?} at row {x}, column {y}")
+            }
+            ParseError::RaggedRow {
+                row,
+                len,
+                expected_len,
+            } => write!(
+                f,
+                "row {row} has {len} cells, but previous rows have {expected_len}"
+            ),
+            ParseError::MissingMarker(marker) => write!(f, "missing {marker} marker"),
+            ParseError::DuplicateMarker {
+                marker,
+                first,
+                second,
+            } => write!(
+                f,
+                "duplicate {marker} marker at row {}, column {} (first seen at row {}, column {})",
+                second.0, second.1, first.0, first.1
+            ),
+        }
+    }
+}
+
+// Plain BFS when every step costs the same, Dijkstra otherwise
 struct PuzzleBfs<'nf, NF> {
     prev: Vec<Vec<(usize, usize)>>,
     dist_from_start: Vec<Vec<usize>>,
-    neighbor_filter: &'nf NF,
+    directions: &'static [(isize, isize)],
+    weighted: bool,
+    step_cost: &'nf NF,
 }
 
 impl<'nf, NF> PuzzleBfs<'nf, NF>
 where
-    NF: Fn((usize, usize), (usize, usize)) -> bool,
+    NF: Fn((usize, usize), (usize, usize)) -> Option<usize>,
 {
-    fn new<'puz>(puzzle: &'puz Puzzle, neighbor_filter: &'nf NF) -> Self {
+    fn new<'puz>(puzzle: &'puz Puzzle, rules: &MovementRules, step_cost: &'nf NF) -> Self {
         let n_rows = puzzle.map.len();
         let n_cols = puzzle.map[0].len();
 
@@ -104,22 +453,35 @@ where
         PuzzleBfs {
             prev,
             dist_from_start,
-            neighbor_filter,
+            directions: rules.directions(),
+            weighted: rules.is_weighted(),
+            step_cost,
         }
     }
 
-    fn expand(&self, (pos_x, pos_y): (usize, usize)) -> impl Iterator<Item = (usize, usize)> + 'nf {
+    fn expand(
+        &self,
+        (pos_x, pos_y): (usize, usize),
+    ) -> impl Iterator<Item = ((usize, usize), usize)> + 'nf {
         let n_rows = self.dist_from_start.len();
         let n_cols = self.dist_from_start[0].len();
-        let nf = self.neighbor_filter;
+        let step_cost = self.step_cost;
 
-        [(-1_isize, 0_isize), (1, 0), (0, -1), (0, 1)]
-            .into_iter()
+        self.directions
+            .iter()
             .map(move |(delta_x, delta_y)| (pos_x as isize + delta_x, pos_y as isize + delta_y))
             .filter(|&(x, y)| x >= 0 && y >= 0)
             .map(|(x, y)| (x as usize, y as usize))
             .filter(move |&(x, y)| x < n_rows && y < n_cols)
-            .filter(move |&(x, y)| nf((x, y), (pos_x, pos_y)))
+            .filter_map(move |(x, y)| Some(((x, y), step_cost((x, y), (pos_x, pos_y))?)))
+    }
+
+    fn into_distance_field(self, origin: (usize, usize)) -> DistanceField {
+        DistanceField {
+            origin,
+            dist: self.dist_from_start,
+            prev: self.prev,
+        }
     }
 
     fn run_to_completion(
@@ -127,6 +489,14 @@ where
         start: (usize, usize),
         stop_condition: impl Fn((usize, usize)) -> bool,
     ) {
+        if self.weighted {
+            self.run_dijkstra(start, stop_condition);
+        } else {
+            self.run_bfs(start, stop_condition);
+        }
+    }
+
+    fn run_bfs(&mut self, start: (usize, usize), stop_condition: impl Fn((usize, usize)) -> bool) {
         let mut queue = VecDeque::with_capacity(1);
         queue.push_back(start);
 
@@ -136,7 +506,7 @@ where
                 break;
             }
 
-            for (neigh_x, neigh_y) in self.expand((pos_x, pos_y)) {
+            for ((neigh_x, neigh_y), _cost) in self.expand((pos_x, pos_y)) {
                 if self.dist_from_start[neigh_x][neigh_y] > self.dist_from_start[pos_x][pos_y] + 1 {
                     self.dist_from_start[neigh_x][neigh_y] = self.dist_from_start[pos_x][pos_y] + 1;
                     self.prev[neigh_x][neigh_y] = (pos_x, pos_y);
@@ -146,4 +516,33 @@ where
             }
         }
     }
+
+    fn run_dijkstra(
+        &mut self,
+        start: (usize, usize),
+        stop_condition: impl Fn((usize, usize)) -> bool,
+    ) {
+        let mut queue = BinaryHeap::with_capacity(1);
+        queue.push(Reverse((0, start)));
+
+        self.dist_from_start[start.0][start.1] = 0;
+        while let Some(Reverse((dist, (pos_x, pos_y)))) = queue.pop() {
+            if dist > self.dist_from_start[pos_x][pos_y] {
+                continue; // stale entry
+            }
+
+            if stop_condition((pos_x, pos_y)) {
+                break;
+            }
+
+            for ((neigh_x, neigh_y), cost) in self.expand((pos_x, pos_y)) {
+                if self.dist_from_start[neigh_x][neigh_y] > dist + cost {
+                    self.dist_from_start[neigh_x][neigh_y] = dist + cost;
+                    self.prev[neigh_x][neigh_y] = (pos_x, pos_y);
+
+                    queue.push(Reverse((dist + cost, (neigh_x, neigh_y))));
+                }
+            }
+        }
+    }
 }
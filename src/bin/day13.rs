@@ -1,37 +1,95 @@
-use std::{cmp::Ordering, io::stdin};
+use std::{
+    cmp::Ordering,
+    fmt::{self, Display, Write},
+    io::{stdin, Read},
+    slice,
+};
 
 use nom::{branch, bytes::complete::tag, combinator, multi, sequence, IResult};
 
 fn main() {
-    let mut packets = stdin()
+    let mut print_sorted = false;
+    let mut explain = false;
+    let mut json_input = false;
+    let mut emit_json = false;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--print-sorted" => print_sorted = true,
+            "--explain" => explain = true,
+            "--json" => json_input = true,
+            "--emit-json" => emit_json = true,
+            _ => panic!("unknown argument: {arg}"),
+        }
+    }
+
+    let mut input = String::new();
+    stdin().read_to_string(&mut input).unwrap();
+
+    if json_input {
+        // normalize to one canonical packet per line, which the views below can borrow from
+        input = canonicalize_json_packets(&input).unwrap_or_else(|e| panic!("{e}"));
+    }
+
+    let packets = input
         .lines()
-        .map(Result::unwrap)
         .filter(|s| !s.is_empty())
-        .map(|s| parse_packet(&s).unwrap().1)
+        .map(|s| parse_packet_view(s).unwrap().1)
         .collect::<Vec<_>>();
 
-    /*
+    if emit_json {
+        for pair in packets.chunks(2) {
+            for packet in pair {
+                let value = serde_json::Value::from(&packet.to_packet());
+                println!("{}", serde_json::to_string_pretty(&value).unwrap());
+            }
+            println!();
+        }
+
+        return;
+    }
+
+    if explain {
+        for (pair_idx, pair) in packets.chunks_exact(2).enumerate() {
+            let (p1, p2) = (pair[0].to_packet(), pair[1].to_packet());
+
+            let mut trace = String::new();
+            let ord = explain_cmp(&p1, &p2, 0, &mut trace).unwrap();
+            debug_assert_eq!(ord, pair[0].cmp(&pair[1]));
+
+            println!("== Pair {} ==\n{trace}", pair_idx + 1);
+        }
+    }
+
     // part1
     println!(
         "{}",
         packets
             .chunks_exact(2)
             .enumerate()
-            .filter(|(_, chunks)| (chunks[0] <= chunks[1]))
+            .filter(|(_, chunks)| chunks[0] <= chunks[1])
             .map(|(pair_idx, _)| pair_idx + 1)
             .sum::<usize>()
     );
-    */
 
-    let div2: Packet = Packet::divider::<2>();
-    let div6: Packet = Packet::divider::<6>();
+    if print_sorted {
+        let mut sorted = packets
+            .iter()
+            .map(|p| p.to_packet())
+            .chain([Packet::divider::<2>(), Packet::divider::<6>()])
+            .collect::<Vec<_>>();
+        sorted.sort_unstable();
 
-    packets.push(div2.clone());
-    packets.push(div6.clone());
-    packets.sort_unstable();
+        for packet in &sorted {
+            println!("{packet:#}");
+        }
+    }
 
-    let div2_idx = packets.iter().position(|p| *p == div2).unwrap() + 1;
-    let div6_idx = packets.iter().position(|p| *p == div6).unwrap() + 1;
+    // part2: no need to sort, a divider's position is given by how many packets come before it
+    let div2 = PacketView("[[2]]");
+    let div6 = PacketView("[[6]]");
+
+    let div2_idx = packets.iter().filter(|p| **p < div2).count() + 1;
+    let div6_idx = packets.iter().filter(|p| **p < div6).count() + 2; // div2 < div6
 
     println!("{}", div2_idx * div6_idx);
 }
@@ -48,6 +106,58 @@ impl Packet {
     }
 }
 
+// `{}` prints the canonical puzzle syntax, which `parse_packet` reads back as the
+// same packet. `{:#}` pretty-prints it, putting each element of a list that holds
+// other lists on its own indented line.
+impl Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            self.fmt_pretty(f, 0)
+        } else {
+            self.fmt_compact(f)
+        }
+    }
+}
+
+impl Packet {
+    fn fmt_compact(&self, f: &mut impl Write) -> fmt::Result {
+        match self {
+            Packet::Integer(i) => write!(f, "{i}"),
+            Packet::List(l) => {
+                f.write_char('[')?;
+                for (idx, p) in l.iter().enumerate() {
+                    if idx != 0 {
+                        f.write_char(',')?;
+                    }
+                    p.fmt_compact(f)?;
+                }
+                f.write_char(']')
+            }
+        }
+    }
+
+    fn fmt_pretty(&self, f: &mut impl Write, depth: usize) -> fmt::Result {
+        const INDENT: &str = "  ";
+
+        match self {
+            Packet::List(l) if l.iter().any(|p| matches!(p, Packet::List(_))) => {
+                f.write_str("[\n")?;
+                for (idx, p) in l.iter().enumerate() {
+                    f.write_str(&INDENT.repeat(depth + 1))?;
+                    p.fmt_pretty(f, depth + 1)?;
+                    if idx + 1 != l.len() {
+                        f.write_char(',')?;
+                    }
+                    f.write_char('\n')?;
+                }
+                f.write_str(&INDENT.repeat(depth))?;
+                f.write_char(']')
+            }
+            _ => self.fmt_compact(f),
+        }
+    }
+}
+
 fn parse_packet(input: &str) -> IResult<&str, Packet> {
     branch::alt((
         combinator::map(nom::character::complete::i64, Packet::Integer),
@@ -62,25 +172,176 @@ fn parse_packet(input: &str) -> IResult<&str, Packet> {
     ))(input)
 }
 
+impl From<&Packet> for serde_json::Value {
+    fn from(packet: &Packet) -> Self {
+        match packet {
+            Packet::Integer(i) => serde_json::Value::from(*i),
+            Packet::List(l) => serde_json::Value::Array(l.iter().map(Into::into).collect()),
+        }
+    }
+}
+
+impl TryFrom<&serde_json::Value> for Packet {
+    type Error = PacketJsonError;
+
+    fn try_from(value: &serde_json::Value) -> Result<Self, Self::Error> {
+        fn convert(
+            value: &serde_json::Value,
+            path: &mut String,
+        ) -> Result<Packet, PacketJsonError> {
+            match value {
+                serde_json::Value::Number(n) => {
+                    n.as_i64()
+                        .map(Packet::Integer)
+                        .ok_or_else(|| PacketJsonError::NotAPacket {
+                            path: path.clone(),
+                            value: value.clone(),
+                        })
+                }
+                serde_json::Value::Array(l) => l
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, v)| {
+                        let path_len = path.len();
+                        write!(path, "[{idx}]").unwrap();
+                        let res = convert(v, path);
+                        path.truncate(path_len);
+                        res
+                    })
+                    .collect::<Result<_, _>>()
+                    .map(Packet::List),
+                _ => Err(PacketJsonError::NotAPacket {
+                    path: path.clone(),
+                    value: value.clone(),
+                }),
+            }
+        }
+
+        convert(value, &mut "$".to_owned())
+    }
+}
+
+#[derive(Debug)]
+enum PacketJsonError {
+    Syntax(serde_json::Error),
+    NotAPacket {
+        path: String,
+        value: serde_json::Value,
+    },
+}
+
+impl Display for PacketJsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PacketJsonError::Syntax(e) => write!(f, "invalid JSON: {e}"),
+            PacketJsonError::NotAPacket { path, value } => write!(
+                f,
+                "packets only hold integers and lists, found {value} at {path}"
+            ),
+        }
+    }
+}
+
+// Reads a stream of whitespace-separated JSON packets (pretty-printed or not),
+// and writes them back one per line in canonical packet syntax.
+fn canonicalize_json_packets(input: &str) -> Result<String, PacketJsonError> {
+    let mut out = String::new();
+    for value in serde_json::Deserializer::from_str(input).into_iter::<serde_json::Value>() {
+        let value = value.map_err(PacketJsonError::Syntax)?;
+        writeln!(out, "{}", Packet::try_from(&value)?).unwrap();
+    }
+
+    Ok(out)
+}
+
 impl Ord for Packet {
     fn cmp(&self, other: &Self) -> Ordering {
         use Packet::*;
         match (self, other) {
             (Integer(i1), Integer(i2)) => i1.cmp(i2),
-            (Integer(i1), p2) => List(vec![Integer(*i1)]).cmp(p2),
-            (p1, Integer(i2)) => p1.cmp(&List(vec![Integer(*i2)])),
+            // an integer compares like a list holding just itself
+            (Integer(_), List(l2)) => compare_packet_list(slice::from_ref(self), l2),
+            (List(l1), Integer(_)) => compare_packet_list(l1, slice::from_ref(other)),
             (List(l1), List(l2)) => compare_packet_list(l1, l2),
         }
     }
 }
 
 fn compare_packet_list(l1: &[Packet], l2: &[Packet]) -> Ordering {
-    match (l1, l2) {
-        ([], []) => Ordering::Equal,
-        ([], _) => Ordering::Less,
-        (_, []) => Ordering::Greater,
-        ([p1, rest1 @ ..], [p2, rest2 @ ..]) => p1.cmp(p2).then(rest1.cmp(rest2)),
+    l1.iter()
+        .zip(l2)
+        .map(|(p1, p2)| p1.cmp(p2))
+        .find(|ord| ord.is_ne())
+        .unwrap_or_else(|| l1.len().cmp(&l2.len()))
+}
+
+// Same comparison as `Ord for Packet`, but writes out each step in the puzzle's
+// notation, down to the element that decided the order.
+fn explain_cmp(
+    p1: &Packet,
+    p2: &Packet,
+    depth: usize,
+    out: &mut impl Write,
+) -> Result<Ordering, fmt::Error> {
+    use Packet::*;
+
+    let indent = "  ".repeat(depth);
+    writeln!(out, "{indent}- Compare {p1} vs {p2}")?;
+
+    let ord = match (p1, p2) {
+        (Integer(i1), Integer(i2)) => i1.cmp(i2),
+        (Integer(i1), p2) => {
+            let p1 = List(vec![Integer(*i1)]);
+            writeln!(
+                out,
+                "{indent}  - Mixed types; convert left to {p1} and retry comparison"
+            )?;
+            return explain_cmp(&p1, p2, depth + 1, out);
+        }
+        (p1, Integer(i2)) => {
+            let p2 = List(vec![Integer(*i2)]);
+            writeln!(
+                out,
+                "{indent}  - Mixed types; convert right to {p2} and retry comparison"
+            )?;
+            return explain_cmp(p1, &p2, depth + 1, out);
+        }
+        (List(l1), List(l2)) => {
+            for (p1, p2) in l1.iter().zip(l2) {
+                let ord = explain_cmp(p1, p2, depth + 1, out)?;
+                if ord != Ordering::Equal {
+                    return Ok(ord);
+                }
+            }
+
+            let ord = l1.len().cmp(&l2.len());
+            match ord {
+                Ordering::Less => writeln!(
+                    out,
+                    "{indent}  - Left side ran out of items, so inputs are in the right order"
+                )?,
+                Ordering::Greater => writeln!(
+                    out,
+                    "{indent}  - Right side ran out of items, so inputs are not in the right order"
+                )?,
+                Ordering::Equal => {}
+            }
+            return Ok(ord);
+        }
+    };
+
+    match ord {
+        Ordering::Less => writeln!(
+            out,
+            "{indent}  - Left side is smaller, so inputs are in the right order"
+        )?,
+        Ordering::Greater => writeln!(
+            out,
+            "{indent}  - Right side is smaller, so inputs are not in the right order"
+        )?,
+        Ordering::Equal => {}
     }
+    Ok(ord)
 }
 
 impl PartialOrd for Packet {
@@ -88,3 +349,170 @@ impl PartialOrd for Packet {
         Some(self.cmp(other))
     }
 }
+
+// Zero-copy view over the text of a well-formed packet, compared in place
+// without building a `Packet`.
+#[derive(Clone, Copy, Debug)]
+struct PacketView<'s>(&'s str);
+
+impl<'s> PacketView<'s> {
+    // elements of a list, or the integer itself as if it were a one-element list
+    fn elements(self) -> PacketViewElements<'s> {
+        let inner = self
+            .0
+            .strip_prefix('[')
+            .and_then(|s| s.strip_suffix(']'))
+            .unwrap_or(self.0);
+
+        PacketViewElements(inner)
+    }
+
+    fn as_integer(self) -> Option<i64> {
+        self.0.parse().ok()
+    }
+
+    fn to_packet(self) -> Packet {
+        parse_packet(self.0).unwrap().1
+    }
+}
+
+struct PacketViewElements<'s>(&'s str);
+
+impl<'s> Iterator for PacketViewElements<'s> {
+    type Item = PacketView<'s>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0.is_empty() {
+            return None;
+        }
+
+        let mut depth = 0;
+        let end = self
+            .0
+            .bytes()
+            .position(|c| {
+                match c {
+                    b'[' => depth += 1,
+                    b']' => depth -= 1,
+                    _ => {}
+                }
+                c == b',' && depth == 0
+            })
+            .unwrap_or(self.0.len());
+
+        let (elem, rest) = self.0.split_at(end);
+        self.0 = rest.strip_prefix(',').unwrap_or(rest);
+
+        Some(PacketView(elem))
+    }
+}
+
+impl Ord for PacketView<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if let (Some(i1), Some(i2)) = (self.as_integer(), other.as_integer()) {
+            return i1.cmp(&i2);
+        }
+
+        let mut l1 = self.elements();
+        let mut l2 = other.elements();
+        loop {
+            match (l1.next(), l2.next()) {
+                (None, None) => return Ordering::Equal,
+                (None, Some(_)) => return Ordering::Less,
+                (Some(_), None) => return Ordering::Greater,
+                (Some(p1), Some(p2)) => match p1.cmp(&p2) {
+                    Ordering::Equal => {}
+                    ord => return ord,
+                },
+            }
+        }
+    }
+}
+
+impl PartialOrd for PacketView<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for PacketView<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for PacketView<'_> {}
+
+fn parse_packet_view(input: &str) -> IResult<&str, PacketView<'_>> {
+    combinator::map(
+        combinator::recognize(branch::alt((
+            combinator::map(nom::character::complete::i64, |_| ()),
+            combinator::map(
+                sequence::delimited(
+                    tag("["),
+                    combinator::opt(sequence::pair(
+                        parse_packet_view,
+                        multi::many0_count(sequence::preceded(tag(","), parse_packet_view)),
+                    )),
+                    tag("]"),
+                ),
+                |_| (),
+            ),
+        ))),
+        PacketView,
+    )(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+[1,1,3,1,1]
+[1,1,5,1,1]
+
+[[1],[2,3,4]]
+[[1],4]
+
+[9]
+[[8,7,6]]
+
+[[4,4],4,4]
+[[4,4],4,4,4]
+
+[7,7,7,7]
+[7,7,7]
+
+[]
+[3]
+
+[[[]]]
+[[]]
+
+[1,[2,[3,[4,[5,6,7]]]],8,9]
+[1,[2,[3,[4,[5,6,0]]]],8,9]
+";
+
+    fn sample_packets() -> Vec<Packet> {
+        SAMPLE
+            .lines()
+            .filter(|s| !s.is_empty())
+            .map(|s| parse_packet(s).unwrap().1)
+            .collect()
+    }
+
+    #[test]
+    fn display_round_trips_through_parser() {
+        for packet in sample_packets() {
+            assert_eq!(parse_packet(&packet.to_string()), Ok(("", packet.clone())));
+        }
+    }
+
+    #[test]
+    fn pretty_display_round_trips_through_json() {
+        for packet in sample_packets() {
+            let canonical = canonicalize_json_packets(&format!("{packet:#}")).unwrap();
+            assert_eq!(canonical, format!("{packet}\n"));
+        }
+    }
+}
@@ -1,105 +1,246 @@
-use std::{collections::HashSet, ops::RangeInclusive};
+use std::{collections::HashSet, fmt};
+
+type Coord = (i64, i64);
 
 fn main() {
+    let mut source = (500, 0);
+    let mut floor_offset = 2;
+    let mut check_part2 = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--source" => {
+                source = parse_coord(&args.next().expect("missing source position"))
+                    .unwrap_or_else(|e| panic!("invalid source position: {e}"));
+            }
+            "--floor-offset" => {
+                floor_offset = args
+                    .next()
+                    .expect("missing floor offset")
+                    .parse()
+                    .expect("invalid floor offset");
+            }
+            "--check" => check_part2 = true,
+            _ => panic!("unknown argument: {arg}"),
+        }
+    }
+
     let input = std::io::stdin()
         .lines()
         .map(Result::unwrap)
-        .filter(|l| !l.is_empty());
-
-    let mut map = input.flat_map(parse_path).collect::<HashSet<_>>();
+        .enumerate()
+        .filter(|(_idx, l)| !l.is_empty());
+
+    let mut rocks = HashSet::new();
+    for (idx, line) in input {
+        let path = parse_path(&line)
+            .unwrap_or_else(|e| panic!("invalid input on line {}: {e} in {line:?}", idx + 1));
+        rocks.extend(path);
+    }
 
-    let floor_y = map.iter().map(|(_x, y)| y).max().unwrap() + 1;
+    let part1_config = SandConfig {
+        source,
+        bottom: Bottom::Abyss,
+    };
+    println!("{}", simulate(&rocks, &part1_config));
+
+    let part2_config = SandConfig {
+        source,
+        bottom: Bottom::Floor {
+            offset: floor_offset,
+        },
+    };
+    let part2 = triangle_fill(&rocks, &part2_config);
+    if check_part2 {
+        assert_eq!(part2, simulate(&rocks, &part2_config));
+    }
+    println!("{part2}");
+}
 
-    let mut part1_done = false;
+#[derive(Clone, Copy, Debug)]
+enum Bottom {
+    // grains that fall below the lowest rock are lost forever
+    Abyss,
+    // an infinite floor sits `offset` rows below the lowest rock
+    Floor { offset: i64 },
+}
 
-    for i in 1.. {
-        let mut grain_pos = (500, 0);
-        while let Some(next) = grain_next_pos(&map, grain_pos) {
-            if grain_pos.1 >= floor_y && !part1_done {
-                println!("{}", i - 1);
-                part1_done = true;
-            }
+#[derive(Clone, Copy, Debug)]
+struct SandConfig {
+    source: Coord,
+    bottom: Bottom,
+}
 
-            if grain_pos.1 == floor_y {
-                break;
+fn simulate(rocks: &HashSet<Coord>, config: &SandConfig) -> usize {
+    let max_y = rocks
+        .iter()
+        .map(|(_x, y)| *y)
+        .max()
+        .unwrap_or(config.source.1);
+
+    let floor_y = match config.bottom {
+        Bottom::Abyss => None,
+        Bottom::Floor { offset } => Some(max_y + offset),
+    };
+
+    let mut map = rocks.clone();
+    let mut n_grains = 0;
+
+    while !map.contains(&config.source) {
+        let mut grain_pos = config.source;
+        while let Some(next) = grain_next_pos(&map, floor_y, grain_pos) {
+            if floor_y.is_none() && next.1 > max_y {
+                return n_grains;
             }
 
             grain_pos = next;
         }
 
-        if grain_pos == (500, 0) {
-            println!("{i}");
-            break;
-        }
-
         map.insert(grain_pos);
+        n_grains += 1;
     }
+
+    n_grains
 }
 
-fn grain_next_pos(map: &HashSet<(usize, usize)>, (x, y): (usize, usize)) -> Option<(usize, usize)> {
-    if !map.contains(&(x, y + 1)) {
-        Some((x, y + 1))
-    } else if !map.contains(&(x.saturating_sub(1), y + 1)) {
-        Some((x - 1, y + 1))
-    } else if !map.contains(&(x + 1, y + 1)) {
-        Some((x + 1, y + 1))
-    } else {
-        None
+// With a floor, sand ends up resting on every cell reachable from the source by
+// falling down or diagonally down, so the cells can be counted row by row
+// instead of simulating each grain.
+fn triangle_fill(rocks: &HashSet<Coord>, config: &SandConfig) -> usize {
+    let Bottom::Floor { offset } = config.bottom else {
+        panic!("triangle fill requires a floor");
+    };
+
+    let max_y = rocks
+        .iter()
+        .map(|(_x, y)| *y)
+        .max()
+        .unwrap_or(config.source.1);
+    let floor_y = max_y + offset;
+
+    if rocks.contains(&config.source) {
+        return 0;
     }
-}
 
-fn parse_path(input: String) -> impl Iterator<Item = (usize, usize)> {
-    let mut input = input.split(" -> ").map(parse_coord);
+    let (source_x, source_y) = config.source;
+    let mut row = vec![true];
+    let mut count = 1;
 
-    #[derive(Debug)]
-    enum Path {
-        Horiz(RangeInclusive<usize>, usize),
-        Vert(usize, RangeInclusive<usize>),
+    // row[i] refers to x = source_x - (y - source_y) + i
+    for y in (source_y + 1)..floor_y {
+        let min_x = source_x - (y - source_y);
+        let next_row = (0..row.len() + 2)
+            .map(|i| {
+                let reached_from_above =
+                    (i.saturating_sub(2)..=i.min(row.len() - 1)).any(|above_idx| row[above_idx]);
+
+                reached_from_above && !rocks.contains(&(min_x + i as i64, y))
+            })
+            .collect::<Vec<_>>();
+
+        count += next_row.iter().filter(|reachable| **reachable).count();
+        row = next_row;
     }
 
-    impl Iterator for Path {
-        type Item = (usize, usize);
-
-        fn next(&mut self) -> Option<Self::Item> {
-            match self {
-                Path::Horiz(x_it, y) => {
-                    let x = x_it.next()?;
-                    Some((x, *y))
-                }
-                Path::Vert(x, y_it) => {
-                    let y = y_it.next()?;
-                    Some((*x, y))
-                }
-            }
+    count
+}
+
+fn grain_next_pos(map: &HashSet<Coord>, floor_y: Option<i64>, (x, y): Coord) -> Option<Coord> {
+    let is_free = |pos: &Coord| !map.contains(pos) && floor_y.is_none_or(|floor_y| pos.1 < floor_y);
+
+    [(x, y + 1), (x - 1, y + 1), (x + 1, y + 1)]
+        .into_iter()
+        .find(is_free)
+}
+
+fn parse_path(input: &str) -> Result<impl Iterator<Item = Coord>, ParseError> {
+    let points = input
+        .split(" -> ")
+        .map(parse_coord)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let segments = match points.as_slice() {
+        [single] => vec![Segment::new(*single, *single)],
+        _ => points
+            .windows(2)
+            .map(|pair| Segment::new(pair[0], pair[1]))
+            .collect(),
+    };
+
+    Ok(segments.into_iter().flatten())
+}
+
+// Bresenham rasterization of a rock segment, endpoints included
+#[derive(Debug)]
+struct Segment {
+    pos: Coord,
+    end: Coord,
+    delta: Coord,
+    step: Coord,
+    err: i64,
+    done: bool,
+}
+
+impl Segment {
+    fn new(start: Coord, end: Coord) -> Self {
+        let delta = ((end.0 - start.0).abs(), -(end.1 - start.1).abs());
+        let step = ((end.0 - start.0).signum(), (end.1 - start.1).signum());
+
+        Segment {
+            pos: start,
+            end,
+            delta,
+            step,
+            err: delta.0 + delta.1,
+            done: false,
         }
     }
+}
 
-    let mut paths = Vec::with_capacity(input.size_hint().0.max(2) - 1);
-    let mut prev = input.next().unwrap();
-    for next in input {
-        let path = if prev.0 == next.0 {
-            let min = prev.1.min(next.1);
-            let max = prev.1.max(next.1);
-            Path::Vert(prev.0, min..=max)
-        } else if prev.1 == next.1 {
-            let min = prev.0.min(next.0);
-            let max = prev.0.max(next.0);
-            Path::Horiz(min..=max, prev.1)
+impl Iterator for Segment {
+    type Item = Coord;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let current = self.pos;
+        if current == self.end {
+            self.done = true;
         } else {
-            unreachable!()
-        };
+            let err2 = 2 * self.err;
+            if err2 >= self.delta.1 {
+                self.err += self.delta.1;
+                self.pos.0 += self.step.0;
+            }
+            if err2 <= self.delta.0 {
+                self.err += self.delta.0;
+                self.pos.1 += self.step.1;
+            }
+        }
 
-        paths.push(path);
-        prev = next;
+        Some(current)
     }
-
-    paths.into_iter().flatten()
 }
 
-fn parse_coord(input: &str) -> (usize, usize) {
-    let (x, y) = input.split_once(',').unwrap();
-    (x.parse().unwrap(), y.parse().unwrap())
+fn parse_coord(input: &str) -> Result<Coord, ParseError> {
+    let err = || ParseError {
+        coord: input.to_string(),
+    };
+
+    let (x, y) = input.trim().split_once(',').ok_or_else(err)?;
+    Ok((x.parse().map_err(|_| err())?, y.parse().map_err(|_| err())?))
 }
 
 #[derive(Debug)]
-struct ParseError;
+struct ParseError {
+    coord: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid coordinate {:?}, expected x,y", self.coord)
+    }
+}
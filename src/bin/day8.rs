@@ -1,6 +1,13 @@
-use take_until::TakeUntilExt;
-
 fn main() {
+    let mut show_best_tree = false;
+
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--best-tree" => show_best_tree = true,
+            _ => panic!("unknown argument: {arg}"),
+        }
+    }
+
     let input = std::io::stdin()
         .lines()
         .map(Result::unwrap)
@@ -16,7 +23,11 @@ fn main() {
     );
 
     // part 2
-    println!("{}", map.compute_scenic_score().flatten().max().unwrap());
+    let ((row, col), score) = map.best_scenic_spot().unwrap();
+    println!("{score}");
+    if show_best_tree {
+        println!("best tree at row {row}, column {col}");
+    }
 }
 
 struct TreeHeightMap(Vec<Vec<i8>>);
@@ -125,53 +136,87 @@ impl TreeHeightMap {
             })
     }
 
+    // looking right from a tree is looking back along the reversed line
     fn compute_lr_view_range(&self) -> impl Iterator<Item = impl Iterator<Item = usize> + '_> {
         self.0.iter().map(|line| {
-            line.iter().enumerate().map(move |(col_idx, &height)| {
-                line.iter()
-                    .skip(col_idx + 1)
-                    .take_until(|&&h| h >= height)
-                    .count()
-            })
+            let mut view_ranges = look_back_view_ranges(line.iter().rev().copied());
+            view_ranges.reverse();
+            view_ranges.into_iter()
         })
     }
 
     fn compute_rl_view_range(&self) -> impl Iterator<Item = impl Iterator<Item = usize> + '_> {
-        self.0.iter().map(|line| {
-            line.iter().enumerate().map(move |(col_idx, &height)| {
-                line.iter()
-                    .rev()
-                    .skip(line.len() - col_idx)
-                    .take_until(|&&h| h >= height)
-                    .count()
-            })
-        })
+        self.0
+            .iter()
+            .map(|line| look_back_view_ranges(line.iter().copied()).into_iter())
     }
 
     fn compute_tb_view_range(&self) -> impl Iterator<Item = impl Iterator<Item = usize> + '_> {
-        self.0.iter().enumerate().map(move |(line_idx, line)| {
-            line.iter().enumerate().map(move |(col_idx, &height)| {
-                self.0
-                    .iter()
-                    .map(|line| line[col_idx])
-                    .skip(line_idx + 1)
-                    .take_until(|&h| h >= height)
-                    .count()
+        let columns = (0..self.0[0].len())
+            .map(|col_idx| {
+                let mut view_ranges =
+                    look_back_view_ranges(self.0.iter().rev().map(|line| line[col_idx]));
+                view_ranges.reverse();
+                view_ranges
             })
-        })
+            .collect::<Vec<_>>();
+
+        transpose(columns)
     }
 
     fn compute_bt_view_range(&self) -> impl Iterator<Item = impl Iterator<Item = usize> + '_> {
-        self.0.iter().enumerate().map(move |(line_idx, line)| {
-            line.iter().enumerate().map(move |(col_idx, &height)| {
-                self.0
-                    .iter()
-                    .map(|line| line[col_idx])
-                    .rev()
-                    .skip(self.0.len() - line_idx)
-                    .take_until(|&h| h >= height)
-                    .count()
+        let columns = (0..self.0[0].len())
+            .map(|col_idx| look_back_view_ranges(self.0.iter().map(|line| line[col_idx])))
+            .collect::<Vec<_>>();
+
+        transpose(columns)
+    }
+
+    // (row, column) of the tree with the highest scenic score, and that score
+    fn best_scenic_spot(&self) -> Option<((usize, usize), usize)> {
+        self.compute_scenic_score()
+            .enumerate()
+            .flat_map(|(line_idx, scores)| {
+                scores
+                    .enumerate()
+                    .map(move |(col_idx, score)| ((line_idx, col_idx), score))
             })
-        })
+            .max_by_key(|(_pos, score)| *score)
     }
 }
+
+// For each tree, how many trees it sees looking back towards the start of `heights`.
+//
+// The stack keeps the trees that could still block the view of a later tree: every
+// tree lower than the current one is hidden behind it for the rest of the line, so
+// each tree is pushed and popped at most once.
+fn look_back_view_ranges(heights: impl Iterator<Item = i8>) -> Vec<usize> {
+    let mut blockers: Vec<(usize, i8)> = Vec::new();
+
+    heights
+        .enumerate()
+        .map(|(idx, height)| {
+            while blockers.last().is_some_and(|&(_, h)| h < height) {
+                blockers.pop();
+            }
+
+            let view_range = match blockers.last() {
+                Some(&(blocker_idx, _)) => idx - blocker_idx,
+                None => idx,
+            };
+            blockers.push((idx, height));
+            view_range
+        })
+        .collect()
+}
+
+fn transpose(columns: Vec<Vec<usize>>) -> impl Iterator<Item = std::vec::IntoIter<usize>> {
+    let n_lines = columns.first().map_or(0, Vec::len);
+    (0..n_lines).map(move |line_idx| {
+        columns
+            .iter()
+            .map(|column| column[line_idx])
+            .collect::<Vec<_>>()
+            .into_iter()
+    })
+}
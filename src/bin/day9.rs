@@ -1,23 +1,106 @@
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
+    fmt,
+    hash::Hash,
     ops::{Add, AddAssign, Div, Mul, Neg, Sub},
     str::FromStr,
 };
 
 fn main() {
+    let mut render_knots = None;
+    let mut knot_stats = None;
+    let mut three_dimensional = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--render" => {
+                render_knots = Some(
+                    args.next()
+                        .expect("missing knot count")
+                        .parse::<usize>()
+                        .expect("invalid knot count"),
+                );
+            }
+            "--knot-stats" => {
+                knot_stats = Some(
+                    args.next()
+                        .expect("missing knot count")
+                        .parse::<usize>()
+                        .expect("invalid knot count"),
+                );
+            }
+            "--3d" => three_dimensional = true,
+            _ => panic!("unknown argument: {arg}"),
+        }
+    }
+
     let movements = std::io::stdin()
         .lines()
         .map(Result::unwrap)
         .filter(|s| !s.is_empty())
-        .map(|s| s.parse::<Movement>().unwrap())
-        .collect::<Vec<_>>();
+        .map(|s| s.parse::<Movement>().unwrap());
+
+    if three_dimensional {
+        assert!(render_knots.is_none(), "--render only supports 2D ropes");
+
+        let movements = movements.collect::<Vec<_>>();
+        if let Some(n_components) = knot_stats {
+            print_visits_per_knot::<Vector3>(&movements, n_components);
+        }
+
+        println!("{}", solve::<Vector3>(&movements, 2));
+        println!("{}", solve::<Vector3>(&movements, 10));
+        return;
+    }
+
+    let movements = movements.inspect(|mov| {
+        if Vector::unit_step(mov.direction).is_none() {
+            panic!("movement {mov} needs --3d");
+        }
+    });
+
+    // without anything to replay, the answers can be computed as the input comes in
+    if render_knots.is_none() && knot_stats.is_none() {
+        let [part1, part2] = solve_streaming(movements);
+        println!("{part1}");
+        println!("{part2}");
+        return;
+    }
+
+    let movements = movements.collect::<Vec<_>>();
+
+    if let Some(n_components) = render_knots {
+        render_moves(&movements, n_components);
+    }
+
+    if let Some(n_components) = knot_stats {
+        print_visits_per_knot::<Vector>(&movements, n_components);
+    }
 
-    println!("{}", solve::<2>(&movements));
-    println!("{}", solve::<10>(&movements));
+    let [part1, part2] = solve_streaming(movements);
+    println!("{part1}");
+    println!("{part2}");
 }
 
-fn solve<const N_COMPONENTS: usize>(movements: &[Movement]) -> usize {
-    let mut rope: Rope<N_COMPONENTS> = Rope::default();
+// Runs the 2 and 10 knot ropes side by side, so each movement is only needed once
+fn solve_streaming(movements: impl IntoIterator<Item = Movement>) -> [usize; 2] {
+    let mut ropes = [Rope::<Vector>::new(2), Rope::new(10)];
+    let mut visited_tail_positions = [VisitedBitmap::default(), VisitedBitmap::default()];
+
+    for mov in movements {
+        for (rope, visited) in ropes.iter_mut().zip(&mut visited_tail_positions) {
+            for tail in rope.translate_head_track_tail(mov) {
+                visited.insert(tail);
+            }
+        }
+    }
+
+    visited_tail_positions.map(|visited| visited.len())
+}
+
+fn solve<P: Position>(movements: &[Movement], n_components: usize) -> usize {
+    let mut rope = Rope::<P>::new(n_components);
 
     let mut visited_tail_positions = HashSet::new();
     for mov in movements.iter().copied() {
@@ -28,72 +111,226 @@ fn solve<const N_COMPONENTS: usize>(movements: &[Movement]) -> usize {
     visited_tail_positions.len()
 }
 
-struct Rope<const N_COMPONENTS: usize> /* where NComponents >= 2, but we can't express that yet */ {
-    components: [Vector; N_COMPONENTS],
+fn print_visits_per_knot<P: Position>(movements: &[Movement], n_components: usize) {
+    for (idx, n_visited) in visits_per_knot::<P>(movements, n_components)
+        .into_iter()
+        .enumerate()
+    {
+        println!("knot {idx}: {n_visited}");
+    }
 }
 
-impl<const N_COMPONENTS: usize> Rope<N_COMPONENTS> {
-    fn translate_head_track_tail(
-        &mut self,
-        movement: Movement,
-    ) -> impl Iterator<Item = Vector> + '_ {
-        struct TailTrackIterator<'rope, const N_COMPONENTS: usize> {
-            rope: &'rope mut Rope<N_COMPONENTS>,
-            movement: Movement,
+// Number of distinct cells visited by each knot, head first
+fn visits_per_knot<P: Position>(movements: &[Movement], n_components: usize) -> Vec<usize> {
+    let mut rope = Rope::<P>::new(n_components);
+    let mut visited = vec![HashSet::from([P::default()]); n_components];
+
+    for mov in movements.iter().copied() {
+        let mut steps = rope.translate_head_track_tail(mov);
+        while steps.next().is_some() {
+            for (knot_visited, knot) in visited.iter_mut().zip(steps.knots()) {
+                knot_visited.insert(*knot);
+            }
         }
+    }
 
-        impl<'rope, const N_COMPONENTS: usize> Iterator for TailTrackIterator<'rope, N_COMPONENTS> {
-            type Item = Vector;
+    visited.iter().map(HashSet::len).collect()
+}
 
-            fn next(&mut self) -> Option<Self::Item> {
-                let new_head = self
-                    .movement
-                    .translate_coord_step(*self.rope.head_coord())?;
-                *self.rope.head_coord() = new_head;
+// Prints the rope after every movement like the puzzle does, followed by the
+// positions visited by the tail
+fn render_moves(movements: &[Movement], n_components: usize) {
+    let mut rope = Rope::<Vector>::new(n_components);
+    let start = *rope.tail_coord();
 
-                for component_idx in 1..N_COMPONENTS {
-                    let (components_prev, components_next) =
-                        self.rope.components.split_at_mut(component_idx);
-                    let prev = &components_prev[component_idx - 1];
-                    let current = &mut components_next[0];
+    let mut states = Vec::new();
+    let mut visited_tail_positions = HashSet::from([start]);
+    for mov in movements.iter().copied() {
+        visited_tail_positions.extend(rope.translate_head_track_tail(mov));
+        states.push((mov, rope.components.clone()));
+    }
 
-                    let update = match *prev - *current {
-                        Vector(x, y) if x.abs() <= 1 && y.abs() <= 1 => Vector(0, 0),
+    // all frames share the same bounds so the rope doesn't jump around
+    let frame = Bounds::enclosing(
+        states
+            .iter()
+            .flat_map(|(_mov, knots)| knots.iter())
+            .chain(&visited_tail_positions),
+    );
+
+    for (mov, knots) in &states {
+        println!("== {mov} ==");
+        println!();
+        print!(
+            "{}",
+            frame.render(|pos| {
+                match knots.iter().position(|knot| *knot == pos) {
+                    Some(0) => 'H',
+                    Some(1) if n_components == 2 => 'T',
+                    Some(idx) => char::from_digit(idx as u32, 36).unwrap_or('?'),
+                    None if pos == start => 's',
+                    None => '.',
+                }
+            })
+        );
+        println!();
+    }
 
-                        Vector(x, 0) => Vector(x.signum(), 0),
-                        Vector(0, y) => Vector(0, y.signum()),
+    let trail = Bounds::enclosing(&visited_tail_positions);
+    println!("== tail trail ==");
+    println!();
+    print!(
+        "{}",
+        trail.render(|pos| match pos {
+            _ if pos == start => 's',
+            _ if visited_tail_positions.contains(&pos) => '#',
+            _ => '.',
+        })
+    );
+    println!();
+}
 
-                        Vector(x, y) => Vector(x.signum(), y.signum()),
-                    };
+#[derive(Debug, Clone, Copy)]
+struct Bounds {
+    min: Vector,
+    max: Vector,
+}
 
-                    *current += update;
-                }
+impl Bounds {
+    fn enclosing<'a>(positions: impl IntoIterator<Item = &'a Vector>) -> Self {
+        positions.into_iter().fold(
+            Bounds {
+                min: Vector(i64::MAX, i64::MAX),
+                max: Vector(i64::MIN, i64::MIN),
+            },
+            |Bounds { min, max }, &Vector(x, y)| Bounds {
+                min: Vector(min.0.min(x), min.1.min(y)),
+                max: Vector(max.0.max(x), max.1.max(y)),
+            },
+        )
+    }
 
-                Some(*self.rope.tail_coord())
-            }
+    // up is positive y, so the top row is printed first
+    fn render(&self, mut cell: impl FnMut(Vector) -> char) -> String {
+        let mut out = String::new();
+        for y in (self.min.1..=self.max.1).rev() {
+            out.extend((self.min.0..=self.max.0).map(|x| cell(Vector(x, y))));
+            out.push('\n');
         }
+        out
+    }
+}
+
+// Set of visited cells stored one bit per cell, in square tiles that are only
+// allocated once the rope gets there, so memory follows the cells actually visited
+// rather than their bounding box.
+#[derive(Debug, Default)]
+struct VisitedBitmap {
+    tiles: HashMap<(i64, i64), [u64; TILE_WORDS]>,
+    len: usize,
+}
+
+const TILE_SIZE: i64 = 16;
+const TILE_WORDS: usize = (TILE_SIZE * TILE_SIZE / 64) as usize;
+
+impl VisitedBitmap {
+    fn insert(&mut self, Vector(x, y): Vector) -> bool {
+        let tile = (x.div_euclid(TILE_SIZE), y.div_euclid(TILE_SIZE));
+        let idx = (y.rem_euclid(TILE_SIZE) * TILE_SIZE + x.rem_euclid(TILE_SIZE)) as usize;
+        let (word, bit) = (idx / 64, 1 << (idx % 64));
+
+        let words = self.tiles.entry(tile).or_insert([0; TILE_WORDS]);
+        let is_new = words[word] & bit == 0;
+        if is_new {
+            words[word] |= bit;
+            self.len += 1;
+        }
+        is_new
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+// Anything a knot can be placed on: the puzzle's plane, or space for 3D ropes
+trait Position:
+    Copy + Eq + Hash + Default + Add<Output = Self> + AddAssign + Sub<Output = Self>
+{
+    fn unit_step(direction: Direction) -> Option<Self>;
+
+    // whether two knots this far apart are touching
+    fn is_touching(self) -> bool;
+
+    fn signum(self) -> Self;
+}
+
+struct Rope<P = Vector> {
+    // always at least two knots: the head and the tail
+    components: Vec<P>,
+}
 
+impl<P: Position> Rope<P> {
+    fn new(n_components: usize) -> Self {
+        assert!(n_components >= 2, "a rope needs at least a head and a tail");
+        Rope {
+            components: vec![P::default(); n_components],
+        }
+    }
+
+    fn translate_head_track_tail(&mut self, movement: Movement) -> TailTrackIterator<'_, P> {
         TailTrackIterator {
             rope: self,
             movement,
         }
     }
 
-    fn head_coord(&mut self) -> &mut Vector {
+    fn head_coord(&mut self) -> &mut P {
         &mut self.components[0]
     }
 
-    fn tail_coord(&mut self) -> &mut Vector {
-        &mut self.components[N_COMPONENTS - 1]
+    fn tail_coord(&mut self) -> &mut P {
+        let tail_idx = self.components.len() - 1;
+        &mut self.components[tail_idx]
     }
 }
 
-// for some reason Default is not implemented for [T; N] yet
-impl<const N_COMPONENTS: usize> Default for Rope<N_COMPONENTS> {
-    fn default() -> Self {
-        Rope {
-            components: [Default::default(); N_COMPONENTS],
+struct TailTrackIterator<'rope, P> {
+    rope: &'rope mut Rope<P>,
+    movement: Movement,
+}
+
+impl<P> TailTrackIterator<'_, P> {
+    // positions of every knot after the last step
+    fn knots(&self) -> &[P] {
+        &self.rope.components
+    }
+}
+
+impl<P: Position> Iterator for TailTrackIterator<'_, P> {
+    type Item = P;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let new_head = self
+            .movement
+            .translate_coord_step(*self.rope.head_coord())?;
+        *self.rope.head_coord() = new_head;
+
+        for component_idx in 1..self.rope.components.len() {
+            let (components_prev, components_next) =
+                self.rope.components.split_at_mut(component_idx);
+            let prev = &components_prev[component_idx - 1];
+            let current = &mut components_next[0];
+
+            // a knot that isn't touching moves one step closer along every axis,
+            // which is straight for aligned knots and diagonal otherwise
+            let delta = *prev - *current;
+            if !delta.is_touching() {
+                *current += delta.signum();
+            }
         }
+
+        Some(*self.rope.tail_coord())
     }
 }
 
@@ -103,12 +340,32 @@ struct Movement {
     amount: u64,
 }
 
+impl fmt::Display for Movement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Direction::*;
+
+        let direction = match self.direction {
+            Up => "U",
+            Down => "D",
+            Left => "L",
+            Right => "R",
+            UpLeft => "UL",
+            UpRight => "UR",
+            DownLeft => "DL",
+            DownRight => "DR",
+            Forward => "F",
+            Backward => "B",
+        };
+        write!(f, "{direction} {}", self.amount)
+    }
+}
+
 impl Movement {
-    fn translate_coord_step(&mut self, coord: Vector) -> Option<Vector> {
+    fn translate_coord_step<P: Position>(&mut self, coord: P) -> Option<P> {
         if self.amount == 0 {
             None
         } else {
-            let dir: Vector = self.direction.into();
+            let dir = P::unit_step(self.direction).expect("direction not available for this rope");
             self.amount -= 1;
             Some(coord + dir)
         }
@@ -121,22 +378,44 @@ enum Direction {
     Down,
     Left,
     Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+    // along the third axis, only for 3D ropes
+    Forward,
+    Backward,
 }
 
-impl From<Direction> for Vector {
-    fn from(val: Direction) -> Self {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+struct Vector(i64, i64);
+
+impl Position for Vector {
+    fn unit_step(direction: Direction) -> Option<Self> {
         use Direction::*;
-        match val {
+        Some(match direction {
             Up => Vector(0, 1),
             Down => Vector(0, -1),
             Left => Vector(-1, 0),
             Right => Vector(1, 0),
-        }
+            UpLeft => Vector(-1, 1),
+            UpRight => Vector(1, 1),
+            DownLeft => Vector(-1, -1),
+            DownRight => Vector(1, -1),
+            Forward | Backward => return None,
+        })
     }
-}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
-struct Vector(i64, i64);
+    fn is_touching(self) -> bool {
+        let Vector(x, y) = self;
+        x.abs() <= 1 && y.abs() <= 1
+    }
+
+    fn signum(self) -> Self {
+        let Vector(x, y) = self;
+        Vector(x.signum(), y.signum())
+    }
+}
 
 impl Add for Vector {
     type Output = Vector;
@@ -190,6 +469,60 @@ impl Div<i64> for Vector {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+struct Vector3(i64, i64, i64);
+
+impl Position for Vector3 {
+    fn unit_step(direction: Direction) -> Option<Self> {
+        match direction {
+            Direction::Forward => Some(Vector3(0, 0, 1)),
+            Direction::Backward => Some(Vector3(0, 0, -1)),
+            _ => {
+                let Vector(x, y) = Vector::unit_step(direction)?;
+                Some(Vector3(x, y, 0))
+            }
+        }
+    }
+
+    fn is_touching(self) -> bool {
+        let Vector3(x, y, z) = self;
+        x.abs() <= 1 && y.abs() <= 1 && z.abs() <= 1
+    }
+
+    fn signum(self) -> Self {
+        let Vector3(x, y, z) = self;
+        Vector3(x.signum(), y.signum(), z.signum())
+    }
+}
+
+impl Add for Vector3 {
+    type Output = Vector3;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        let Vector3(x1, y1, z1) = self;
+        let Vector3(x2, y2, z2) = rhs;
+
+        Vector3(x1 + x2, y1 + y2, z1 + z2)
+    }
+}
+
+impl AddAssign for Vector3 {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Vector3 {
+    type Output = Vector3;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        let Vector3(x1, y1, z1) = self;
+        let Vector3(x2, y2, z2) = rhs;
+
+        Vector3(x1 - x2, y1 - y2, z1 - z2)
+    }
+}
+
 impl FromStr for Movement {
     type Err = ParseErr;
 
@@ -215,6 +548,12 @@ impl FromStr for Direction {
             "D" => Down,
             "L" => Left,
             "R" => Right,
+            "UL" => UpLeft,
+            "UR" => UpRight,
+            "DL" => DownLeft,
+            "DR" => DownRight,
+            "F" => Forward,
+            "B" => Backward,
             _ => return Err(ParseErr),
         })
     }